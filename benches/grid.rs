@@ -0,0 +1,162 @@
+//! Solver and draw timings on the 70x50 test puzzle
+//!
+//! Run with `cargo bench` (draw timings need the "gui" feature). Drawing goes to a null
+//! graphics back-end, so only the work done by `PictureGridView::draw` is measured.
+
+extern crate fill_a_pix_rust;
+#[cfg(feature = "gui")]
+extern crate piston_window;
+
+use std::time::{Duration, Instant};
+
+use fill_a_pix_rust::{count_solutions, load_file, PictureGrid, Solver};
+
+/// Puzzle used for every timing
+const PUZZLE: &str = "test_files/medium-plus-70x50.json";
+
+/// Runs the closure repeatedly for at least a second and prints the average time per run
+fn bench<F: FnMut()>(name: &str, mut f: F) {
+    // warm up
+    f();
+    let target = Duration::from_secs(1);
+    let start = Instant::now();
+    let mut runs = 0u32;
+    while start.elapsed() < target {
+        f();
+        runs += 1;
+    }
+    let per_run = start.elapsed() / runs;
+    println!("{:<32} {:>12.3?} per run ({} runs)", name, per_run, runs);
+}
+
+fn main() {
+    let path = format!("{}/{}", env!("CARGO_MANIFEST_DIR"), PUZZLE);
+    let picgrid = load_file(&path).expect("unable to load benchmark puzzle");
+
+    bench("solve (sweep only)", || {
+        Solver::new(picgrid.clone()).with_search(false).solve();
+    });
+    bench("solve", || {
+        Solver::new(picgrid.clone()).with_search(true).solve();
+    });
+    bench("count_solutions (limit 2)", || {
+        count_solutions(&picgrid, 2);
+    });
+    bench("validate", || {
+        picgrid.validate();
+    });
+
+    draw::run(&picgrid);
+}
+
+#[cfg(not(feature = "gui"))]
+mod draw {
+    use PictureGrid;
+
+    pub fn run(_picgrid: &PictureGrid) {
+        println!("draw timings skipped (built without the \"gui\" feature)");
+    }
+}
+
+#[cfg(feature = "gui")]
+mod draw {
+    use bench;
+    use piston_window::character::{Character, CharacterCache};
+    use piston_window::context::Context;
+    use piston_window::draw_state::DrawState;
+    use piston_window::types::{Color, FontSize};
+    use piston_window::{Graphics, ImageSize, Viewport};
+    use PictureGrid;
+
+    use fill_a_pix_rust::{
+        PictureGridController, PictureGridView, PictureGridViewSettings, Solver,
+    };
+
+    /// Texture without any pixels
+    pub struct NullTexture;
+
+    impl ImageSize for NullTexture {
+        fn get_size(&self) -> (u32, u32) {
+            (0, 0)
+        }
+    }
+
+    /// Graphics back-end that only counts vertices
+    pub struct NullGraphics {
+        pub vertices: usize,
+    }
+
+    impl Graphics for NullGraphics {
+        type Texture = NullTexture;
+
+        fn clear_color(&mut self, _color: Color) {}
+
+        fn clear_stencil(&mut self, _value: u8) {}
+
+        fn tri_list<F>(&mut self, _draw_state: &DrawState, _color: &[f32; 4], mut f: F)
+        where
+            F: FnMut(&mut dyn FnMut(&[[f32; 2]])),
+        {
+            let vertices = &mut self.vertices;
+            f(&mut |chunk| *vertices += chunk.len());
+        }
+
+        fn tri_list_uv<F>(
+            &mut self,
+            _draw_state: &DrawState,
+            _color: &[f32; 4],
+            _texture: &NullTexture,
+            mut f: F,
+        ) where
+            F: FnMut(&mut dyn FnMut(&[[f32; 2]], &[[f32; 2]])),
+        {
+            let vertices = &mut self.vertices;
+            f(&mut |chunk, _uv| *vertices += chunk.len());
+        }
+    }
+
+    /// Character cache with fixed size glyphs
+    pub struct NullGlyphs {
+        texture: NullTexture,
+    }
+
+    impl CharacterCache for NullGlyphs {
+        type Texture = NullTexture;
+        type Error = ();
+
+        fn character(
+            &mut self,
+            font_size: FontSize,
+            _ch: char,
+        ) -> Result<Character<'_, NullTexture>, ()> {
+            let size = f64::from(font_size);
+            Ok(Character {
+                offset: [0.0, size],
+                size: [size * 0.6, size],
+                texture: &self.texture,
+            })
+        }
+    }
+
+    pub fn run(picgrid: &PictureGrid) {
+        let viewport = Viewport {
+            rect: [0, 0, 1440, 900],
+            draw_size: [1440, 900],
+            window_size: [1440, 900],
+        };
+        let context = Context::new_viewport(viewport);
+        let mut glyphs = NullGlyphs {
+            texture: NullTexture,
+        };
+        let mut graphics = NullGraphics { vertices: 0 };
+
+        let (solved, _report) = Solver::new(picgrid.clone()).solve();
+        for (name, grid) in [("draw (unsolved)", picgrid), ("draw (solved)", &solved)].iter() {
+            let controller = PictureGridController::new((*grid).clone());
+            let mut view = PictureGridView::new(PictureGridViewSettings::new());
+            bench(name, || {
+                view.draw(&controller, &mut glyphs, &context, &mut graphics);
+            });
+        }
+    }
+}
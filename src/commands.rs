@@ -0,0 +1,565 @@
+//! Commands that run without opening a window.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use fill_a_pix_rust::sheet::{PAGE_A4, PAGE_LETTER};
+use fill_a_pix_rust::{
+    count_solutions, import_image, load_file, to_text, CellState, Difficulty, Generator,
+    ImageFormat, LoadError, PictureExport, PictureGrid, PictureGridViewSettings, PuzzleSheet,
+    SheetFormat, Solver,
+};
+
+use load_or_exit;
+
+/// Exit code used when a puzzle could not be fully solved.
+pub const EXIT_UNSOLVED: i32 = 2;
+/// Exit code used when a puzzle does not have exactly one solution.
+pub const EXIT_NOT_UNIQUE: i32 = 2;
+/// Exit code used when a puzzle file has problems.
+pub const EXIT_INVALID: i32 = 2;
+
+/// Returns true if the path has a text format extension (`.txt`)
+fn is_text_path(path: &str) -> bool {
+    Path::new(path).extension().is_some_and(|ext| ext == "txt")
+}
+
+/// Writes the grid to the file (or as JSON to stdout when there is no file).
+///
+/// Files ending in `.txt` are written in the text format, anything else as JSON.
+/// Returns the process exit code.
+fn write_puzzle(picgrid: &PictureGrid, output: Option<&String>) -> i32 {
+    let written = match output {
+        Some(output) if is_text_path(output) => {
+            if picgrid.metadata.is_some() || picgrid.solution.is_some() {
+                eprintln!("Warning: the text format leaves out the save metadata and solution");
+            }
+            fs::write(output, to_text(picgrid)).map_err(|err| err.to_string())
+        }
+        Some(output) => picgrid.save(output).map_err(|err| err.to_string()),
+        None => serde_json::to_writer(io::stdout(), picgrid).map_err(|err| err.to_string()),
+    };
+    match written {
+        Ok(()) => {
+            if let Some(output) = output {
+                eprintln!("{} written!", output);
+            }
+            0
+        }
+        Err(err) => {
+            let error_code = 1;
+            eprintln!(
+                "Error({}): Unable to write {}: {}",
+                error_code,
+                output.map_or("output", |output| output.as_str()),
+                err
+            );
+            error_code
+        }
+    }
+}
+
+/// Solves a puzzle and prints the result as ASCII (or writes it as JSON).
+///
+/// Returns the process exit code: 0 when fully solved, `EXIT_UNSOLVED` otherwise.
+pub fn solve(args: &[String]) -> i32 {
+    let mut filename: Option<&String> = None;
+    let mut output: Option<&String> = None;
+    let mut search = true;
+    let mut valid = true;
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--output" | "-o" => match iter.next() {
+                Some(val) => output = Some(val),
+                None => valid = false,
+            },
+            "--sweep-only" => search = false,
+            // unknown flags and extra file names
+            _ if arg.starts_with('-') || filename.is_some() => valid = false,
+            _ => filename = Some(arg),
+        }
+    }
+
+    let filename = match filename {
+        Some(filename) if valid => filename,
+        _ => {
+            let error_code = 1;
+            eprintln!(
+                "Error({}): Usage: solve <puzzle.json> [--output <solved.json>] [--sweep-only]",
+                error_code
+            );
+            return error_code;
+        }
+    };
+
+    let picgrid = load_or_exit(filename);
+    let (solved, report) = Solver::new(picgrid).with_search(search).solve();
+    eprintln!(
+        "{} cells decided in {} passes ({} clue overlaps, {} guesses, {} backtracks)",
+        report.cells_decided, report.passes, report.overlaps, report.guesses, report.backtracks
+    );
+
+    if output.is_some() {
+        let error_code = write_puzzle(&solved, output);
+        if error_code != 0 {
+            return error_code;
+        }
+    } else {
+        print!("{}", solved);
+    }
+
+    if report.is_solved {
+        0
+    } else {
+        EXIT_UNSOLVED
+    }
+}
+
+/// Reports whether a puzzle has zero, one or many solutions.
+///
+/// When there are several, two differing solutions and the cells where they disagree are
+/// printed. Returns the process exit code: 0 when unique, `EXIT_NOT_UNIQUE` otherwise.
+pub fn unique(args: &[String]) -> i32 {
+    let mut filename: Option<&String> = None;
+    let mut limit = 2;
+    let mut valid = true;
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--limit" => match iter.next().and_then(|val| val.parse().ok()) {
+                Some(val) if val >= 2 => limit = val,
+                _ => valid = false,
+            },
+            _ => filename = Some(arg),
+        }
+    }
+
+    let filename = match filename {
+        Some(filename) if valid => filename,
+        _ => {
+            let error_code = 1;
+            eprintln!(
+                "Error({}): Usage: unique <puzzle.json> [--limit <count (2 or more)>]",
+                error_code
+            );
+            return error_code;
+        }
+    };
+
+    let picgrid = load_or_exit(filename);
+    let result = count_solutions(&picgrid, limit);
+    if result.count() == 0 {
+        println!("No solution");
+    } else if result.is_unique() {
+        println!("Unique solution");
+        print!("{}", result.solutions[0]);
+    } else {
+        if result.reached_limit {
+            println!("At least {} solutions", result.count());
+        } else {
+            println!("{} solutions", result.count());
+        }
+        println!("Solution 1:");
+        print!("{}", result.solutions[0]);
+        println!("Solution 2:");
+        print!("{}", result.solutions[1]);
+        let differences: Vec<String> = result
+            .differences()
+            .iter()
+            .map(|pos| format!("({},{})", pos[0], pos[1]))
+            .collect();
+        println!("Differing cells: {}", differences.join(" "));
+    }
+
+    if result.is_unique() {
+        0
+    } else {
+        EXIT_NOT_UNIQUE
+    }
+}
+
+/// Generates a unique-solution puzzle and writes it as JSON.
+///
+/// Returns the process exit code.
+pub fn generate(args: &[String]) -> i32 {
+    let usage = "generate <width> <height> [--seed <number>] [--difficulty easy|medium|hard] \
+                 [--picture <solved.json>] [--output <puzzle.json>]\n\
+                 (the difficulty is the hardest the puzzle may be: hard is unique, any difficulty)";
+    let mut size: Vec<u16> = Vec::new();
+    let mut seed = 0;
+    let mut difficulty = Difficulty::Medium;
+    let mut picture: Option<&String> = None;
+    let mut output: Option<&String> = None;
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        let parsed = match arg.as_str() {
+            "--seed" => iter
+                .next()
+                .and_then(|val| val.parse().ok())
+                .map(|val| seed = val),
+            "--difficulty" => iter
+                .next()
+                .and_then(|val| val.parse().ok())
+                .map(|val| difficulty = val),
+            "--picture" => iter.next().map(|val| picture = Some(val)),
+            "--output" | "-o" => iter.next().map(|val| output = Some(val)),
+            _ => arg.parse().ok().map(|val| size.push(val)),
+        };
+        if parsed.is_none() {
+            size.clear();
+            break;
+        }
+    }
+
+    if size.len() != 2 {
+        let error_code = 1;
+        eprintln!("Error({}): Usage: {}", error_code, usage);
+        return error_code;
+    }
+    if let Err(err) = PictureGrid::check_size(size[0], size[1]) {
+        let error_code = 1;
+        eprintln!("Error({}): Unsupported size: {}", error_code, err);
+        return error_code;
+    }
+
+    let mut generator = Generator::new(size[0], size[1], seed).with_difficulty(difficulty);
+    if let Some(picture) = picture {
+        let solved = load_or_exit(picture);
+        if solved.width != size[0] || solved.height != size[1] {
+            let error_code = 1;
+            eprintln!(
+                "Error({}): Picture {} is {}x{}, not {}x{}",
+                error_code, picture, solved.width, solved.height, size[0], size[1]
+            );
+            return error_code;
+        }
+        let mut shaded = Vec::with_capacity(size[0] as usize * size[1] as usize);
+        for y in 0..solved.height as isize {
+            for x in 0..solved.width as isize {
+                shaded.push(matches!(solved.get(x, y), Some(CellState::Shaded(_hint))));
+            }
+        }
+        generator = generator.with_picture(shaded);
+    }
+
+    match generator.generate() {
+        Some(picgrid) => write_puzzle(&picgrid, output),
+        None => {
+            let error_code = 1;
+            eprintln!(
+                "Error({}): Unable to generate a {} puzzle",
+                error_code, difficulty
+            );
+            error_code
+        }
+    }
+}
+
+/// Creates a puzzle from a PBM, PGM or BMP image and writes it as JSON.
+///
+/// Returns the process exit code.
+pub fn import(args: &[String]) -> i32 {
+    let usage = "import <image> [--threshold <0-255>] [--minimize] [--output <puzzle.json>]";
+    let mut filename: Option<&String> = None;
+    let mut threshold = 128;
+    let mut minimize = false;
+    let mut output: Option<&String> = None;
+    let mut valid = true;
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--threshold" => match iter.next().and_then(|val| val.parse().ok()) {
+                Some(val) => threshold = val,
+                None => valid = false,
+            },
+            "--minimize" => minimize = true,
+            "--output" | "-o" => output = iter.next(),
+            _ => filename = Some(arg),
+        }
+    }
+
+    let filename = match filename {
+        Some(filename) if valid => filename,
+        _ => {
+            let error_code = 1;
+            eprintln!("Error({}): Usage: {}", error_code, usage);
+            return error_code;
+        }
+    };
+
+    eprintln!("Importing {}...", filename);
+    match import_image(filename, threshold, minimize) {
+        Ok(picgrid) => {
+            if !count_solutions(&picgrid, 2).is_unique() {
+                eprintln!("Warning: the puzzle does not have a unique solution");
+            }
+            write_puzzle(&picgrid, output)
+        }
+        Err(err) => {
+            let error_code = 1;
+            eprintln!(
+                "Error({}): Unable to import {}: {}",
+                error_code, filename, err
+            );
+            error_code
+        }
+    }
+}
+
+/// Number of schema problems listed per file before the rest are summarized.
+const MAX_LISTED_PROBLEMS: usize = 20;
+
+/// Collects the puzzle files to check (directories contribute their `.json` and `.txt` files).
+fn puzzle_files(path: &Path, files: &mut Vec<PathBuf>) -> io::Result<()> {
+    if path.is_dir() {
+        let mut entries: Vec<PathBuf> = fs::read_dir(path)?
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|entry| {
+                entry.is_file()
+                    && entry
+                        .extension()
+                        .is_some_and(|ext| ext == "json" || ext == "txt")
+            })
+            .collect();
+        entries.sort();
+        files.extend(entries);
+    } else {
+        files.push(path.to_path_buf());
+    }
+    Ok(())
+}
+
+/// Validates puzzle files (or directories of puzzle files), listing every problem found.
+///
+/// Returns the process exit code: 0 when every file is valid, `EXIT_INVALID` otherwise.
+pub fn check(args: &[String]) -> i32 {
+    if args.is_empty() {
+        let error_code = 1;
+        eprintln!(
+            "Error({}): Usage: check <puzzle.json or directory>...",
+            error_code
+        );
+        return error_code;
+    }
+
+    let mut files = Vec::new();
+    for arg in args {
+        if let Err(err) = puzzle_files(Path::new(arg), &mut files) {
+            let error_code = 1;
+            eprintln!("Error({}): Unable to read {}: {}", error_code, arg, err);
+            return error_code;
+        }
+    }
+
+    let mut num_invalid = 0;
+    for file in files.iter() {
+        match load_file(file) {
+            Ok(_picgrid) => println!("OK   {}", file.display()),
+            Err(LoadError::Schema(errors)) => {
+                num_invalid += 1;
+                let plural = if errors.len() == 1 { "" } else { "s" };
+                println!(
+                    "FAIL {}: {} problem{}",
+                    file.display(),
+                    errors.len(),
+                    plural
+                );
+                for err in errors.iter().take(MAX_LISTED_PROBLEMS) {
+                    println!("       {}", err);
+                }
+                if errors.len() > MAX_LISTED_PROBLEMS {
+                    println!("       ... and {} more", errors.len() - MAX_LISTED_PROBLEMS);
+                }
+            }
+            Err(err) => {
+                num_invalid += 1;
+                println!("FAIL {}: {}", file.display(), err);
+            }
+        }
+    }
+
+    println!(
+        "{} of {} files valid",
+        files.len() - num_invalid,
+        files.len()
+    );
+    if num_invalid == 0 {
+        0
+    } else {
+        EXIT_INVALID
+    }
+}
+
+/// Converts a puzzle between JSON and the text format (chosen by the output extension).
+///
+/// Returns the process exit code.
+pub fn convert(args: &[String]) -> i32 {
+    if args.len() != 2 {
+        let error_code = 1;
+        eprintln!(
+            "Error({}): Usage: convert <input> <output.json or output.txt>",
+            error_code
+        );
+        return error_code;
+    }
+    let picgrid = load_or_exit(&args[0]);
+    write_puzzle(&picgrid, Some(&args[1]))
+}
+
+/// Exports the picture of a puzzle (solving it first if needed) as a PNG or SVG image.
+///
+/// Returns the process exit code.
+pub fn export(args: &[String]) -> i32 {
+    let usage = "export <puzzle.json> <output.png or output.svg> [--clues] [--cell-size <pixels>]";
+    let mut paths: Vec<&String> = Vec::new();
+    let mut clues = false;
+    let mut cell_size: Option<u32> = None;
+    let mut valid = true;
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--clues" => clues = true,
+            "--cell-size" => match iter.next().and_then(|val| val.parse().ok()) {
+                Some(val) if val > 0 => cell_size = Some(val),
+                _ => valid = false,
+            },
+            _ => paths.push(arg),
+        }
+    }
+
+    if !valid || paths.len() != 2 {
+        let error_code = 1;
+        eprintln!("Error({}): Usage: {}", error_code, usage);
+        return error_code;
+    }
+    let (filename, output) = (paths[0], paths[1]);
+    if ImageFormat::from_path(output).is_none() {
+        let error_code = 1;
+        eprintln!(
+            "Error({}): Unable to export {}: expected a .png or .svg file",
+            error_code, output
+        );
+        return error_code;
+    }
+
+    let mut picgrid = load_or_exit(filename);
+    if !picgrid.is_solved() {
+        let (solved, report) = Solver::new(picgrid).with_search(true).solve();
+        if !report.is_solved {
+            eprintln!("Warning: the puzzle could not be fully solved");
+        }
+        picgrid = solved;
+    }
+
+    let settings = PictureGridViewSettings::new();
+    let mut picture = PictureExport::new(&picgrid, &settings).with_clues(clues);
+    if let Some(cell_size) = cell_size {
+        picture = picture.with_cell_size(cell_size);
+    }
+    match picture.save(output) {
+        Ok(()) => {
+            eprintln!("{} written!", output);
+            0
+        }
+        Err(err) => {
+            let error_code = 1;
+            eprintln!("Error({}): Unable to write {}: {}", error_code, output, err);
+            error_code
+        }
+    }
+}
+
+/// Writes a printable sheet of a puzzle (optionally with an answer key) as SVG or PDF.
+///
+/// Returns the process exit code: `EXIT_UNSOLVED` if an answer key is asked for but the puzzle
+/// has no solution.
+pub fn sheet(args: &[String]) -> i32 {
+    let usage = "sheet <puzzle.json> <output.pdf or output.svg> [--answers] \
+                 [--page a4|letter] [--margin <points>] [--cell-size <points>]";
+    let mut paths: Vec<&String> = Vec::new();
+    let mut answers = false;
+    let mut page_size = PAGE_A4;
+    let mut margin: Option<f64> = None;
+    let mut cell_size: Option<f64> = None;
+    let mut valid = true;
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--answers" => answers = true,
+            "--page" => match iter.next().map(|val| val.to_lowercase()) {
+                Some(ref val) if val == "a4" => page_size = PAGE_A4,
+                Some(ref val) if val == "letter" => page_size = PAGE_LETTER,
+                _ => valid = false,
+            },
+            "--margin" => match iter.next().and_then(|val| val.parse().ok()) {
+                Some(val) if val >= 0.0 => margin = Some(val),
+                _ => valid = false,
+            },
+            "--cell-size" => match iter.next().and_then(|val| val.parse().ok()) {
+                Some(val) if val > 0.0 => cell_size = Some(val),
+                _ => valid = false,
+            },
+            _ => paths.push(arg),
+        }
+    }
+
+    if !valid || paths.len() != 2 {
+        let error_code = 1;
+        eprintln!("Error({}): Usage: {}", error_code, usage);
+        return error_code;
+    }
+    let (filename, output) = (paths[0], paths[1]);
+    if SheetFormat::from_path(output).is_none() {
+        let error_code = 1;
+        eprintln!(
+            "Error({}): Unable to write {}: expected a .pdf or .svg file",
+            error_code, output
+        );
+        return error_code;
+    }
+
+    let picgrid = load_or_exit(filename);
+    let solved = if answers {
+        // solved from the clues alone, so the marks of a saved game do not matter
+        let mut clues = picgrid.clone();
+        for cell in clues.cells.iter_mut() {
+            *cell = CellState::Unsolved(cell.hint());
+        }
+        let (solved, report) = Solver::new(clues).with_search(true).solve();
+        if !report.is_solved {
+            eprintln!(
+                "Error({}): Unable to write an answer key: {} has no solution",
+                EXIT_UNSOLVED, filename
+            );
+            return EXIT_UNSOLVED;
+        }
+        Some(solved)
+    } else {
+        None
+    };
+
+    let title = Path::new(filename)
+        .file_stem()
+        .map_or(String::new(), |stem| stem.to_string_lossy().into_owned());
+    let mut sheet = PuzzleSheet::new(&picgrid)
+        .with_title(&title)
+        .with_page_size(page_size)
+        .with_cell_size(cell_size)
+        .with_answers(solved.as_ref());
+    if let Some(margin) = margin {
+        sheet = sheet.with_margin(margin);
+    }
+    match sheet.save(output) {
+        Ok(()) => {
+            eprintln!("{} written!", output);
+            0
+        }
+        Err(err) => {
+            let error_code = 1;
+            eprintln!("Error({}): Unable to write {}: {}", error_code, output, err);
+            error_code
+        }
+    }
+}
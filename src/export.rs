@@ -0,0 +1,412 @@
+//! PNG and SVG export of picture grids.
+
+use std::fs::File;
+use std::io;
+use std::io::Write;
+use std::path::Path;
+
+use png;
+
+use picgrid::{CellState, PictureGrid};
+use view_settings::{hint_text_layout, Color, PictureGridViewSettings};
+
+/// Enumeration of export image formats
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ImageFormat {
+    /// Portable Network Graphics
+    Png,
+    /// Scalable Vector Graphics
+    Svg,
+}
+
+impl ImageFormat {
+    /// Finds the format from the file extension (`.png` or `.svg`)
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Option<ImageFormat> {
+        let extension = path.as_ref().extension()?.to_str()?.to_lowercase();
+        match extension.as_str() {
+            "png" => Some(ImageFormat::Png),
+            "svg" => Some(ImageFormat::Svg),
+            _ => None,
+        }
+    }
+}
+
+/// Digits 0-9 as 3x5 pixel glyphs (one row per entry, most significant bit on the left)
+const DIGIT_GLYPHS: [[u8; 5]; 10] = [
+    [0b111, 0b101, 0b101, 0b101, 0b111],
+    [0b010, 0b110, 0b010, 0b010, 0b111],
+    [0b111, 0b001, 0b111, 0b100, 0b111],
+    [0b111, 0b001, 0b111, 0b001, 0b111],
+    [0b101, 0b101, 0b111, 0b001, 0b001],
+    [0b111, 0b100, 0b111, 0b001, 0b111],
+    [0b111, 0b100, 0b111, 0b101, 0b111],
+    [0b111, 0b001, 0b001, 0b001, 0b001],
+    [0b111, 0b101, 0b111, 0b101, 0b111],
+    [0b111, 0b101, 0b111, 0b001, 0b111],
+];
+
+/// Converts a color to 8-bit RGBA
+fn rgba8(color: Color) -> [u8; 4] {
+    let component = |value: f32| (value.clamp(0.0, 1.0) * 255.0).round() as u8;
+    [
+        component(color[0]),
+        component(color[1]),
+        component(color[2]),
+        component(color[3]),
+    ]
+}
+
+/// Formats a color as an SVG paint attribute (with opacity when not opaque)
+fn svg_paint(attribute: &str, color: Color) -> String {
+    let [red, green, blue, alpha] = rgba8(color);
+    let mut paint = format!("{}=\"#{:02x}{:02x}{:02x}\"", attribute, red, green, blue);
+    if alpha < 255 {
+        paint.push_str(&format!(" {}-opacity=\"{:.3}\"", attribute, color[3]));
+    }
+    paint
+}
+
+/// Largest number of pixels an exported image may have (a 16384x16384 image, 1 GiB as RGBA)
+pub const MAX_PIXELS: u64 = 1 << 28;
+
+/// RGBA pixels of an image being drawn
+struct Canvas {
+    width: u32,
+    height: u32,
+    pixels: Vec<u8>,
+}
+
+impl Canvas {
+    /// Creates a canvas filled with the color.
+    ///
+    /// Fails (rather than aborting) if the pixels do not fit in memory.
+    fn new(width: u32, height: u32, color: Color) -> io::Result<Canvas> {
+        let rgba = rgba8(color);
+        let len = width as usize * height as usize * 4;
+        let mut pixels = Vec::new();
+        pixels.try_reserve_exact(len).map_err(|_err| {
+            io::Error::new(
+                io::ErrorKind::OutOfMemory,
+                format!("not enough memory for a {}x{} image", width, height),
+            )
+        })?;
+        pixels.extend(rgba.iter().cloned().cycle().take(len));
+        Ok(Canvas {
+            width,
+            height,
+            pixels,
+        })
+    }
+
+    /// Fills a rectangle (clipped to the canvas)
+    fn fill_rect(&mut self, x: u32, y: u32, width: u32, height: u32, color: Color) {
+        let rgba = rgba8(color);
+        for row in y..(y + height).min(self.height) {
+            for column in x..(x + width).min(self.width) {
+                let offset = (row as usize * self.width as usize + column as usize) * 4;
+                self.pixels[offset..offset + 4].copy_from_slice(&rgba);
+            }
+        }
+    }
+
+    /// Draws a digit centered on (x,y), `scale` pixels per glyph pixel
+    fn draw_digit(&mut self, digit: u8, x: u32, y: u32, scale: u32, color: Color) {
+        let glyph = DIGIT_GLYPHS[digit as usize % 10];
+        let left = x.saturating_sub(scale * 3 / 2);
+        let top = y.saturating_sub(scale * 5 / 2);
+        for (row, bits) in glyph.iter().enumerate() {
+            for column in 0..3 {
+                if bits & (0b100 >> column) != 0 {
+                    self.fill_rect(
+                        left + column * scale,
+                        top + row as u32 * scale,
+                        scale,
+                        scale,
+                        color,
+                    );
+                }
+            }
+        }
+    }
+}
+
+/// Renders a picture grid (and optionally its clues) as an image.
+pub struct PictureExport<'a> {
+    /// Grid to render
+    picgrid: &'a PictureGrid,
+    /// Colors (and default cell size) to render with
+    settings: &'a PictureGridViewSettings,
+    /// Width/height of a cell in pixels
+    cell_size: u32,
+    /// Determines if the clues and grid lines are drawn
+    clues: bool,
+}
+
+impl<'a> PictureExport<'a> {
+    /// Creates a new export of the picture only, with the cell size of the settings.
+    pub fn new(
+        picgrid: &'a PictureGrid,
+        settings: &'a PictureGridViewSettings,
+    ) -> PictureExport<'a> {
+        PictureExport {
+            picgrid,
+            settings,
+            cell_size: settings.cell_size.round().max(1.0) as u32,
+            clues: false,
+        }
+    }
+
+    /// Sets the width/height of a cell in pixels
+    pub fn with_cell_size(mut self, cell_size: u32) -> Self {
+        self.cell_size = cell_size.max(1);
+        self
+    }
+
+    /// Sets if the clues and grid lines are drawn on top of the picture
+    pub fn with_clues(mut self, clues: bool) -> Self {
+        self.clues = clues;
+        self
+    }
+
+    /// Returns the background and hint text colors of a cell
+    fn cell_colors(&self, state: CellState) -> (Color, Color) {
+        let settings = self.settings;
+        match state {
+            CellState::Unsolved(_hint) => (
+                settings.cell_unsolved_background_color,
+                settings.cell_unsolved_hint_text_color,
+            ),
+            CellState::Shaded(_hint) => (
+                settings.cell_solved_shaded_background_color,
+                settings.cell_solved_shaded_hint_text_color,
+            ),
+            CellState::Unshaded(_hint) => (
+                settings.cell_solved_unshaded_background_color,
+                settings.cell_solved_unshaded_hint_text_color,
+            ),
+        }
+    }
+
+    /// Returns the width of the outer border in pixels (none without clues)
+    fn border(&self) -> u32 {
+        if self.clues {
+            self.settings.grid_border_width.round().max(1.0) as u32
+        } else {
+            0
+        }
+    }
+
+    /// Returns the image width and height in pixels.
+    ///
+    /// Fails if the image would have more than `MAX_PIXELS` pixels.
+    pub fn size(&self) -> io::Result<(u32, u32)> {
+        let borders = self.border().checked_mul(2);
+        let side = |cells: u16| {
+            u32::from(cells)
+                .checked_mul(self.cell_size)
+                .and_then(|size| size.checked_add(borders?))
+        };
+        match (side(self.picgrid.width), side(self.picgrid.height)) {
+            (Some(width), Some(height)) if u64::from(width) * u64::from(height) <= MAX_PIXELS => {
+                Ok((width, height))
+            }
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "{}x{} grid with {} pixel cells is too large (at most {} pixels are supported)",
+                    self.picgrid.width, self.picgrid.height, self.cell_size, MAX_PIXELS
+                ),
+            )),
+        }
+    }
+
+    /// Renders the image as SVG (see `size` for when it fails)
+    pub fn to_svg(&self) -> io::Result<String> {
+        let settings = self.settings;
+        let (width, height) = self.size()?;
+        let border = self.border();
+        let cell_size = self.cell_size;
+        let mut svg = format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" \
+             viewBox=\"0 0 {} {}\">\n",
+            width, height, width, height
+        );
+
+        let (hint_size, hint_offset) = hint_text_layout(f64::from(cell_size));
+        let cell_stroke = if self.clues {
+            format!(
+                " {} stroke-width=\"{}\"",
+                svg_paint("stroke", settings.grid_border_color),
+                settings.cell_border_width
+            )
+        } else {
+            String::new()
+        };
+        for y in 0..self.picgrid.height as isize {
+            for x in 0..self.picgrid.width as isize {
+                let state = match self.picgrid.get(x, y) {
+                    Some(state) => state,
+                    None => continue,
+                };
+                let (background, text) = self.cell_colors(state);
+                let left = border + x as u32 * cell_size;
+                let top = border + y as u32 * cell_size;
+                svg.push_str(&format!(
+                    "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" {}{}/>\n",
+                    left,
+                    top,
+                    cell_size,
+                    cell_size,
+                    svg_paint("fill", background),
+                    cell_stroke
+                ));
+                if self.clues && state.hint() < PictureGrid::EMPTY {
+                    svg.push_str(&format!(
+                        "<text x=\"{:.1}\" y=\"{:.1}\" font-family=\"sans-serif\" \
+                         font-size=\"{:.1}\" {}>{}</text>\n",
+                        f64::from(left) + hint_offset[0],
+                        f64::from(top) + hint_offset[1],
+                        hint_size,
+                        svg_paint("fill", text),
+                        state.hint()
+                    ));
+                }
+            }
+        }
+
+        if self.clues {
+            svg.push_str(&format!(
+                "<rect x=\"{:.1}\" y=\"{:.1}\" width=\"{}\" height=\"{}\" fill=\"none\" {} \
+                 stroke-width=\"{}\"/>\n",
+                f64::from(border) / 2.0,
+                f64::from(border) / 2.0,
+                width - border,
+                height - border,
+                svg_paint("stroke", settings.grid_border_color),
+                border
+            ));
+        }
+        svg.push_str("</svg>\n");
+        Ok(svg)
+    }
+
+    /// Renders the image as RGBA pixels (row-major)
+    fn render(&self) -> io::Result<Canvas> {
+        let settings = self.settings;
+        let (width, height) = self.size()?;
+        let border = self.border();
+        let cell_size = self.cell_size;
+        let mut canvas = Canvas::new(width, height, settings.grid_border_color)?;
+
+        let line = settings.cell_border_width.round().max(1.0) as u32;
+        // digits are about 60% of the cell high, and skipped when they cannot fit
+        let scale = cell_size * 3 / 25;
+        for y in 0..self.picgrid.height as isize {
+            for x in 0..self.picgrid.width as isize {
+                let state = match self.picgrid.get(x, y) {
+                    Some(state) => state,
+                    None => continue,
+                };
+                let (background, text) = self.cell_colors(state);
+                let left = border + x as u32 * cell_size;
+                let top = border + y as u32 * cell_size;
+                canvas.fill_rect(left, top, cell_size, cell_size, background);
+                if self.clues {
+                    // grid lines on the left and top of inner cells
+                    if x > 0 {
+                        canvas.fill_rect(left, top, line, cell_size, settings.grid_border_color);
+                    }
+                    if y > 0 {
+                        canvas.fill_rect(left, top, cell_size, line, settings.grid_border_color);
+                    }
+                    if state.hint() < PictureGrid::EMPTY && scale > 0 {
+                        canvas.draw_digit(
+                            state.hint(),
+                            left + cell_size / 2,
+                            top + cell_size / 2,
+                            scale,
+                            text,
+                        );
+                    }
+                }
+            }
+        }
+        Ok(canvas)
+    }
+
+    /// Writes the image as PNG
+    pub fn write_png<W: Write>(&self, writer: W) -> io::Result<()> {
+        let canvas = self.render()?;
+        let mut encoder = png::Encoder::new(writer, canvas.width, canvas.height);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(&canvas.pixels)?;
+        Ok(())
+    }
+
+    /// Writes the image to a file, in the format of its extension (`.png` or `.svg`)
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        // an image that is too large leaves no empty file behind
+        self.size()?;
+        match ImageFormat::from_path(&path) {
+            Some(ImageFormat::Png) => self.write_png(File::create(path)?),
+            Some(ImageFormat::Svg) => {
+                let svg = self.to_svg()?;
+                File::create(path)?.write_all(svg.as_bytes())
+            }
+            None => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "unsupported image format (expected .png or .svg)",
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn size_includes_border_with_clues() {
+        let picgrid = PictureGrid::new(5, 3);
+        let settings = PictureGridViewSettings::new();
+        let picture = PictureExport::new(&picgrid, &settings).with_cell_size(10);
+        assert_eq!(picture.size().unwrap(), (50, 30));
+        let picture = picture.with_clues(true);
+        assert_eq!(picture.size().unwrap(), (56, 36));
+    }
+
+    #[test]
+    fn oversized_images_are_rejected() {
+        let picgrid = PictureGrid::new(5, 3);
+        let settings = PictureGridViewSettings::new();
+        let picture = PictureExport::new(&picgrid, &settings).with_cell_size(u32::MAX);
+        let err = picture.size().unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        assert!(picture.to_svg().is_err());
+        assert!(picture.write_png(Vec::new()).is_err());
+
+        // 4096x4096 cells of 50 pixels would need about 167 GB of pixels
+        let picgrid = PictureGrid::new(4096, 4096);
+        let picture = PictureExport::new(&picgrid, &settings);
+        assert!(picture.size().is_err());
+        let picture = picture.with_cell_size(4);
+        assert_eq!(picture.size().unwrap(), (16384, 16384));
+        assert!(picture.with_cell_size(5).size().is_err());
+    }
+
+    #[test]
+    fn png_has_image_size() {
+        let picgrid = PictureGrid::new(2, 1);
+        let settings = PictureGridViewSettings::new();
+        let picture = PictureExport::new(&picgrid, &settings)
+            .with_cell_size(20)
+            .with_clues(true);
+        let mut png = Vec::new();
+        picture.write_png(&mut png).unwrap();
+        assert_eq!(&png[1..4], b"PNG");
+        // width and height of the IHDR chunk
+        assert_eq!(&png[16..24], &[0, 0, 0, 46, 0, 0, 0, 26]);
+    }
+}
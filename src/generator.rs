@@ -0,0 +1,314 @@
+//! Puzzle generator.
+
+use std::fmt;
+use std::str::FromStr;
+
+use picgrid::{CellState, PictureGrid};
+use solver::{count_solutions, Solver};
+
+/// Enumeration of puzzle difficulties
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Difficulty {
+    /// Solvable with the single clue rules alone
+    Easy,
+    /// Solvable with the single clue and clue overlap rules
+    Medium,
+    /// Has a unique solution, of any difficulty (it may need guessing, or none at all)
+    Hard,
+}
+
+impl fmt::Display for Difficulty {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Difficulty::Easy => write!(f, "easy"),
+            Difficulty::Medium => write!(f, "medium"),
+            Difficulty::Hard => write!(f, "hard"),
+        }
+    }
+}
+
+impl FromStr for Difficulty {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Difficulty, String> {
+        match s.to_lowercase().as_str() {
+            "easy" => Ok(Difficulty::Easy),
+            "medium" => Ok(Difficulty::Medium),
+            "hard" => Ok(Difficulty::Hard),
+            _ => Err(format!("unknown difficulty '{}'", s)),
+        }
+    }
+}
+
+/// Small xorshift random number generator, so a seed always produces the same puzzle.
+struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    fn new(seed: u64) -> Rng {
+        // the state must never be zero
+        Rng {
+            state: (seed ^ 0x9E37_79B9_7F4A_7C15).max(1),
+        }
+    }
+
+    fn next(&mut self) -> u64 {
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 7;
+        self.state ^= self.state << 17;
+        self.state
+    }
+
+    /// Returns a value in 0..bound
+    fn below(&mut self, bound: usize) -> usize {
+        (self.next() % bound as u64) as usize
+    }
+
+    /// Returns a value in 0.0..1.0
+    fn fraction(&mut self) -> f64 {
+        (self.next() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+/// Creates a puzzle with every clue of the picture (shaded cells are `true`, row-major).
+///
+/// All cells are unsolved and the picture is kept as the solution; cells outside of the picture
+/// count as unshaded.
+///
+/// # Panics
+///
+/// Panics if the picture does not hold width * height cells.
+pub fn clues_from_picture(width: u16, height: u16, picture: &[bool]) -> PictureGrid {
+    assert_eq!(
+        picture.len(),
+        width as usize * height as usize,
+        "picture does not match the {}x{} grid",
+        width,
+        height
+    );
+    let is_shaded = |x: isize, y: isize| -> bool {
+        x >= 0
+            && x < width as isize
+            && y >= 0
+            && y < height as isize
+            && picture[(y * width as isize + x) as usize]
+    };
+
+    let mut picgrid = PictureGrid::new(width, height);
+    for y in 0..height as isize {
+        for x in 0..width as isize {
+            let mut hint = 0;
+            for b in (y - 1)..(y + 2) {
+                for a in (x - 1)..(x + 2) {
+                    if is_shaded(a, b) {
+                        hint += 1;
+                    }
+                }
+            }
+            picgrid.set(x, y, CellState::Unsolved(hint));
+        }
+    }
+    picgrid.solution = Some(picture.to_vec());
+    picgrid
+}
+
+/// Finds if the puzzle can be solved at the difficulty (or easier)
+pub fn meets_difficulty(picgrid: &PictureGrid, difficulty: Difficulty) -> bool {
+    match difficulty {
+        Difficulty::Easy | Difficulty::Medium => {
+            let (_solved, report) = Solver::new(picgrid.clone()).solve();
+            report.is_solved && (difficulty != Difficulty::Easy || report.overlaps == 0)
+        }
+        Difficulty::Hard => count_solutions(picgrid, 2).is_unique(),
+    }
+}
+
+/// Generates unique-solution puzzles.
+pub struct Generator {
+    /// Width of generated grid.
+    width: u16,
+    /// Height of generated grid.
+    height: u16,
+    /// Seed of the random number generator.
+    seed: u64,
+    /// Hardest difficulty the puzzle may need.
+    difficulty: Difficulty,
+    /// Fraction of cells shaded in a random picture.
+    density: f64,
+    /// Picture to use instead of a random one (shaded cells are `true`, row-major).
+    picture: Option<Vec<bool>>,
+    /// Number of random pictures tried before giving up.
+    attempts: u32,
+}
+
+impl Generator {
+    /// Creates a new generator of medium puzzles.
+    pub fn new(width: u16, height: u16, seed: u64) -> Generator {
+        Generator {
+            width,
+            height,
+            seed,
+            difficulty: Difficulty::Medium,
+            density: 0.5,
+            picture: None,
+            attempts: 20,
+        }
+    }
+
+    /// Sets the hardest difficulty the puzzle may need
+    pub fn with_difficulty(mut self, difficulty: Difficulty) -> Generator {
+        self.difficulty = difficulty;
+        self
+    }
+
+    /// Sets the fraction of cells shaded in a random picture
+    pub fn with_density(mut self, density: f64) -> Generator {
+        self.density = density;
+        self
+    }
+
+    /// Sets the picture to use instead of a random one (`generate` fails unless it holds
+    /// width * height cells)
+    pub fn with_picture(mut self, picture: Vec<bool>) -> Generator {
+        self.picture = Some(picture);
+        self
+    }
+
+    /// Creates a random picture, smoothed so that it forms shapes rather than noise
+    fn random_picture(&self, rng: &mut Rng) -> Vec<bool> {
+        let (width, height) = (self.width as isize, self.height as isize);
+        let noise: Vec<bool> = (0..(width * height))
+            .map(|_index| rng.fraction() < self.density)
+            .collect();
+
+        let mut picture = Vec::with_capacity(noise.len());
+        for y in 0..height {
+            for x in 0..width {
+                let mut shaded = 0;
+                let mut total = 0;
+                for b in (y - 1).max(0)..(y + 2).min(height) {
+                    for a in (x - 1).max(0)..(x + 2).min(width) {
+                        total += 1;
+                        if noise[(b * width + a) as usize] {
+                            shaded += 1;
+                        }
+                    }
+                }
+                picture.push(shaded * 2 > total);
+            }
+        }
+        picture
+    }
+
+    /// Removes clues (in random order) as long as the puzzle still meets the difficulty
+    fn remove_clues(&self, mut picgrid: PictureGrid, rng: &mut Rng) -> PictureGrid {
+        let mut order: Vec<usize> = (0..picgrid.cells.len()).collect();
+        for i in (1..order.len()).rev() {
+            order.swap(i, rng.below(i + 1));
+        }
+
+        for index in order {
+            let cell = picgrid.cells[index];
+            picgrid.cells[index] = CellState::Unsolved(PictureGrid::EMPTY);
+            if !meets_difficulty(&picgrid, self.difficulty) {
+                picgrid.cells[index] = cell;
+            }
+        }
+        picgrid
+    }
+
+    /// Generates a puzzle whose unique solution is the picture.
+    ///
+    /// Returns None if no picture meets the difficulty, even with every clue shown (or if the
+    /// size is not supported, see `PictureGrid::check_size`, or the picture does not hold
+    /// width * height cells).
+    pub fn generate(&self) -> Option<PictureGrid> {
+        PictureGrid::check_size(self.width, self.height).ok()?;
+        if let Some(ref picture) = self.picture {
+            if picture.len() != self.width as usize * self.height as usize {
+                return None;
+            }
+        }
+        let mut rng = Rng::new(self.seed);
+        let attempts = if self.picture.is_some() {
+            1
+        } else {
+            self.attempts
+        };
+
+        for _attempt in 0..attempts {
+            let picture = match self.picture {
+                Some(ref picture) => picture.clone(),
+                None => self.random_picture(&mut rng),
+            };
+            let picgrid = clues_from_picture(self.width, self.height, &picture);
+            if meets_difficulty(&picgrid, self.difficulty) {
+                return Some(self.remove_clues(picgrid, &mut rng));
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn same_seed_generates_same_puzzle() {
+        let first = Generator::new(8, 6, 42).generate().unwrap();
+        let second = Generator::new(8, 6, 42).generate().unwrap();
+        assert_eq!(first.cells, second.cells);
+        assert_eq!(first.solution, second.solution);
+
+        let other = Generator::new(8, 6, 43).generate().unwrap();
+        assert_ne!(first.cells, other.cells);
+    }
+
+    #[test]
+    fn generated_puzzles_have_unique_solution() {
+        for &difficulty in [Difficulty::Easy, Difficulty::Medium, Difficulty::Hard].iter() {
+            for seed in 0..3 {
+                let picgrid = Generator::new(6, 5, seed)
+                    .with_difficulty(difficulty)
+                    .generate()
+                    .unwrap();
+                assert!(meets_difficulty(&picgrid, difficulty));
+                let count = count_solutions(&picgrid, 2);
+                assert!(count.is_unique());
+
+                let solved = &count.solutions[0];
+                let shaded: Vec<bool> = solved
+                    .cells
+                    .iter()
+                    .map(|cell| matches!(cell, CellState::Shaded(_)))
+                    .collect();
+                assert_eq!(picgrid.solution, Some(shaded));
+            }
+        }
+    }
+
+    #[test]
+    fn picture_is_kept_as_solution() {
+        let picture = vec![true, true, false, true, true, false];
+        let picgrid = Generator::new(3, 2, 0)
+            .with_difficulty(Difficulty::Hard)
+            .with_picture(picture.clone())
+            .generate()
+            .unwrap();
+        assert_eq!(picgrid.solution, Some(picture));
+    }
+
+    #[test]
+    fn picture_of_wrong_size_is_rejected() {
+        let generator = Generator::new(3, 2, 0).with_picture(vec![true; 5]);
+        assert!(generator.generate().is_none());
+    }
+
+    #[test]
+    #[should_panic(expected = "picture does not match the 3x2 grid")]
+    fn clues_from_picture_of_wrong_size_panics() {
+        clues_from_picture(3, 2, &[true; 7]);
+    }
+}
@@ -0,0 +1,182 @@
+//! Undo/redo history of cell edits.
+
+use picgrid::{CellState, PictureGrid};
+use solver::Deduction;
+
+/// A change of a single cell
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct CellChange {
+    /// Column of the cell
+    pub x: isize,
+    /// Row of the cell
+    pub y: isize,
+    /// Cell value before the change
+    pub before: CellState,
+    /// Cell value after the change
+    pub after: CellState,
+}
+
+/// Stores edit actions so they can be undone and redone.
+///
+/// Each action groups the cell changes that are undone together (such as the cells
+/// forced by a single solver step).
+#[derive(Default)]
+pub struct EditHistory {
+    /// Actions that can be undone (most recent last)
+    undo_actions: Vec<Vec<CellChange>>,
+    /// Actions that can be redone (most recently undone last)
+    redo_actions: Vec<Vec<CellChange>>,
+}
+
+impl EditHistory {
+    /// Creates a new empty history.
+    pub fn new() -> EditHistory {
+        EditHistory::default()
+    }
+
+    /// Records an action (ignored when empty), discarding anything that could be redone
+    pub fn record(&mut self, changes: Vec<CellChange>) {
+        if !changes.is_empty() {
+            self.undo_actions.push(changes);
+            self.redo_actions.clear();
+        }
+    }
+
+    /// Records the cells forced by a deduction, which must already be applied to the grid
+    pub fn record_deduction(&mut self, picgrid: &PictureGrid, deduction: &Deduction) {
+        let changes = deduction
+            .cells
+            .iter()
+            .filter_map(|(pos, _state)| {
+                let after = picgrid.get(pos[0], pos[1])?;
+                Some(CellChange {
+                    x: pos[0],
+                    y: pos[1],
+                    before: CellState::Unsolved(after.hint()),
+                    after,
+                })
+            })
+            .collect();
+        self.record(changes);
+    }
+
+    /// Returns the number of actions that can be undone
+    pub fn undo_len(&self) -> usize {
+        self.undo_actions.len()
+    }
+
+    /// Returns the number of actions that can be redone
+    pub fn redo_len(&self) -> usize {
+        self.redo_actions.len()
+    }
+
+    /// Reverts the most recent action. Returns false if there is nothing to undo.
+    pub fn undo(&mut self, picgrid: &mut PictureGrid) -> bool {
+        match self.undo_actions.pop() {
+            Some(changes) => {
+                for change in changes.iter().rev() {
+                    picgrid.set(change.x, change.y, change.before);
+                }
+                self.redo_actions.push(changes);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Reapplies the most recently undone action. Returns false if there is nothing to redo.
+    pub fn redo(&mut self, picgrid: &mut PictureGrid) -> bool {
+        match self.redo_actions.pop() {
+            Some(changes) => {
+                for change in changes.iter() {
+                    picgrid.set(change.x, change.y, change.after);
+                }
+                self.undo_actions.push(changes);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solver::find_deduction;
+    use text_format::parse_text;
+
+    /// Changes the cell at (x,y) of the grid, returning the change
+    fn change(picgrid: &mut PictureGrid, x: isize, y: isize, after: CellState) -> CellChange {
+        let before = picgrid.get(x, y).unwrap();
+        picgrid.set(x, y, after);
+        CellChange {
+            x,
+            y,
+            before,
+            after,
+        }
+    }
+
+    #[test]
+    fn undo_and_redo_actions() {
+        let mut picgrid = parse_text("1.\n").unwrap();
+        let original = picgrid.clone();
+        let mut history = EditHistory::new();
+        let first = change(&mut picgrid, 0, 0, CellState::Shaded(1));
+        history.record(vec![first]);
+        let second = change(&mut picgrid, 1, 0, CellState::Unshaded(PictureGrid::EMPTY));
+        history.record(vec![second]);
+        let edited = picgrid.clone();
+        assert_eq!((history.undo_len(), history.redo_len()), (2, 0));
+
+        assert!(history.undo(&mut picgrid));
+        assert_eq!(picgrid.get(1, 0), Some(second.before));
+        assert_eq!(picgrid.get(0, 0), Some(first.after));
+        assert!(history.undo(&mut picgrid));
+        assert_eq!(picgrid.cells, original.cells);
+        assert!(!history.undo(&mut picgrid));
+        assert_eq!((history.undo_len(), history.redo_len()), (0, 2));
+
+        assert!(history.redo(&mut picgrid));
+        assert!(history.redo(&mut picgrid));
+        assert!(!history.redo(&mut picgrid));
+        assert_eq!(picgrid.cells, edited.cells);
+    }
+
+    #[test]
+    fn recording_clears_redo() {
+        let mut picgrid = parse_text("..\n").unwrap();
+        let mut history = EditHistory::new();
+        let shaded = change(&mut picgrid, 0, 0, CellState::Shaded(PictureGrid::EMPTY));
+        history.record(vec![shaded]);
+        history.undo(&mut picgrid);
+        assert_eq!(history.redo_len(), 1);
+
+        // an empty action is ignored and keeps what can be redone
+        history.record(Vec::new());
+        assert_eq!((history.undo_len(), history.redo_len()), (0, 1));
+
+        let unshaded = change(&mut picgrid, 1, 0, CellState::Unshaded(PictureGrid::EMPTY));
+        history.record(vec![unshaded]);
+        assert_eq!((history.undo_len(), history.redo_len()), (1, 0));
+        assert!(!history.redo(&mut picgrid));
+    }
+
+    #[test]
+    fn solver_step_is_undone_as_one_action() {
+        let mut picgrid = parse_text("0..\n...\n").unwrap();
+        let original = picgrid.clone();
+        let mut history = EditHistory::new();
+        let deduction = find_deduction(&picgrid).unwrap();
+        assert_eq!(deduction.cells.len(), 4);
+        deduction.apply(&mut picgrid);
+        history.record_deduction(&picgrid, &deduction);
+        assert_eq!(history.undo_len(), 1);
+
+        history.undo(&mut picgrid);
+        assert_eq!(picgrid.cells, original.cells);
+        history.redo(&mut picgrid);
+        assert_eq!(picgrid.num_unsolved_cells(), 2);
+        assert_eq!(picgrid.get(0, 0), Some(CellState::Unshaded(0)));
+    }
+}
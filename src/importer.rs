@@ -0,0 +1,506 @@
+//! Importer that turns black-and-white images into puzzles.
+//!
+//! Supports PBM (P1/P4), PGM (P2/P5) and uncompressed BMP (1, 8, 24 or 32 bits per pixel).
+
+use std::error::Error;
+use std::fmt;
+use std::fs::File;
+use std::io;
+use std::io::Read;
+use std::path::Path;
+
+use generator::{clues_from_picture, Difficulty, Generator};
+use picgrid::PictureGrid;
+
+/// Enumeration of image import errors
+#[derive(Debug)]
+pub enum ImportError {
+    /// The image could not be read
+    Io(io::Error),
+    /// The image is not in a supported format (or is corrupt)
+    Format(String),
+}
+
+impl fmt::Display for ImportError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ImportError::Io(err) => write!(f, "{}", err),
+            ImportError::Format(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl Error for ImportError {}
+
+impl From<io::Error> for ImportError {
+    fn from(err: io::Error) -> ImportError {
+        ImportError::Io(err)
+    }
+}
+
+fn format_err<T>(message: &str) -> Result<T, ImportError> {
+    Err(ImportError::Format(message.to_string()))
+}
+
+/// Grayscale image.
+pub struct Bitmap {
+    /// Width of image.
+    pub width: u16,
+    /// Height of image.
+    pub height: u16,
+    /// Luminance of the pixels (0 is black, 255 is white), row-major from the top.
+    pub pixels: Vec<u8>,
+}
+
+impl Bitmap {
+    /// Returns which pixels are darker than the threshold (those become shaded cells)
+    pub fn threshold(&self, level: u8) -> Vec<bool> {
+        self.pixels.iter().map(|pixel| *pixel < level).collect()
+    }
+}
+
+/// Converts image dimensions, rejecting images that cannot be a grid
+fn dimensions(width: i64, height: i64) -> Result<(u16, u16), ImportError> {
+    if width <= 0 || height <= 0 || width > u16::MAX as i64 || height > u16::MAX as i64 {
+        return format_err(&format!("unsupported image size {}x{}", width, height));
+    }
+    let (width, height) = (width as u16, height as u16);
+    if let Err(err) = PictureGrid::check_size(width, height) {
+        return format_err(&format!("unsupported image size: {}", err));
+    }
+    Ok((width, height))
+}
+
+/// Reads the whitespace separated tokens of a netpbm header (skipping comments)
+struct NetpbmHeader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> NetpbmHeader<'a> {
+    fn token(&mut self) -> Result<&'a str, ImportError> {
+        loop {
+            match self.bytes.get(self.pos) {
+                Some(b'#') => {
+                    while self.pos < self.bytes.len() && self.bytes[self.pos] != b'\n' {
+                        self.pos += 1;
+                    }
+                }
+                Some(byte) if byte.is_ascii_whitespace() => self.pos += 1,
+                Some(_) => break,
+                None => return format_err("unexpected end of image header"),
+            }
+        }
+        let start = self.pos;
+        while self.pos < self.bytes.len() && !self.bytes[self.pos].is_ascii_whitespace() {
+            self.pos += 1;
+        }
+        ::std::str::from_utf8(&self.bytes[start..self.pos])
+            .or_else(|_| format_err("invalid image header"))
+    }
+
+    fn number(&mut self) -> Result<i64, ImportError> {
+        self.token()?
+            .parse()
+            .or_else(|_| format_err("invalid number in image header"))
+    }
+
+    /// Returns the binary data following the single whitespace after the header
+    fn data(&self) -> &'a [u8] {
+        &self.bytes[(self.pos + 1).min(self.bytes.len())..]
+    }
+}
+
+/// Parses a PBM or PGM image
+fn parse_netpbm(bytes: &[u8]) -> Result<Bitmap, ImportError> {
+    let mut header = NetpbmHeader { bytes, pos: 0 };
+    let magic = header.token()?;
+    let (width, height) = dimensions(header.number()?, header.number()?)?;
+    let count = width as usize * height as usize;
+    let maxval = match magic {
+        "P1" | "P4" => 1,
+        _ => header.number()?,
+    };
+    if maxval <= 0 || maxval > 65535 {
+        return format_err("invalid maximum gray value");
+    }
+    let scale = |value: i64| -> u8 { (value.min(maxval) * 255 / maxval) as u8 };
+
+    let pixels: Vec<u8> = match magic {
+        "P1" => {
+            // bits may be written without whitespace between them
+            header
+                .data()
+                .iter()
+                .filter(|byte| **byte == b'0' || **byte == b'1')
+                .map(|byte| if *byte == b'1' { 0 } else { 255 })
+                .take(count)
+                .collect()
+        }
+        "P4" => {
+            let stride = (width as usize).div_ceil(8);
+            let data = header.data();
+            if data.len() < stride * height as usize {
+                return format_err("image data is truncated");
+            }
+            let mut pixels = Vec::with_capacity(count);
+            for y in 0..height as usize {
+                for x in 0..width as usize {
+                    let byte = data[y * stride + x / 8];
+                    let bit = (byte >> (7 - (x % 8))) & 1;
+                    pixels.push(if bit == 1 { 0 } else { 255 });
+                }
+            }
+            pixels
+        }
+        "P2" => {
+            let mut pixels = Vec::with_capacity(count);
+            for _index in 0..count {
+                pixels.push(scale(header.number()?));
+            }
+            pixels
+        }
+        "P5" => {
+            let data = header.data();
+            if maxval < 256 {
+                data.iter()
+                    .take(count)
+                    .map(|val| scale(*val as i64))
+                    .collect()
+            } else {
+                data.chunks(2)
+                    .filter(|pair| pair.len() == 2)
+                    .take(count)
+                    .map(|pair| scale(((pair[0] as i64) << 8) | pair[1] as i64))
+                    .collect()
+            }
+        }
+        _ => return format_err("unsupported netpbm format"),
+    };
+
+    if pixels.len() < count {
+        return format_err("image data is truncated");
+    }
+    Ok(Bitmap {
+        width,
+        height,
+        pixels,
+    })
+}
+
+fn read_u16(bytes: &[u8], offset: usize) -> Result<u16, ImportError> {
+    match bytes.get(offset..offset + 2) {
+        Some(b) => Ok(u16::from(b[0]) | (u16::from(b[1]) << 8)),
+        None => format_err("bitmap header is truncated"),
+    }
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> Result<u32, ImportError> {
+    match bytes.get(offset..offset + 4) {
+        Some(b) => Ok(u32::from(b[0])
+            | (u32::from(b[1]) << 8)
+            | (u32::from(b[2]) << 16)
+            | (u32::from(b[3]) << 24)),
+        None => format_err("bitmap header is truncated"),
+    }
+}
+
+fn luminance(red: u8, green: u8, blue: u8) -> u8 {
+    ((299 * red as u32 + 587 * green as u32 + 114 * blue as u32) / 1000) as u8
+}
+
+/// Parses an uncompressed BMP image
+fn parse_bmp(bytes: &[u8]) -> Result<Bitmap, ImportError> {
+    let data_offset = read_u32(bytes, 10)? as usize;
+    let dib_size = read_u32(bytes, 14)? as usize;
+    let raw_width = read_u32(bytes, 18)? as i32;
+    let raw_height = read_u32(bytes, 22)? as i32;
+    let bits = read_u16(bytes, 28)?;
+    let compression = read_u32(bytes, 30)?;
+    if compression != 0 {
+        return format_err("compressed bitmaps are not supported");
+    }
+    if bits != 1 && bits != 8 && bits != 24 && bits != 32 {
+        return format_err("only 1, 8, 24 and 32 bit bitmaps are supported");
+    }
+    // a negative height means the rows are stored top-down
+    let top_down = raw_height < 0;
+    let (width, height) = dimensions(raw_width as i64, (raw_height as i64).abs())?;
+
+    let mut palette = Vec::new();
+    if bits <= 8 {
+        // a bitmap never needs more colors than its bit depth allows
+        let used = read_u32(bytes, 46)? as usize;
+        let colors = if used == 0 || used > 1 << bits {
+            1 << bits
+        } else {
+            used
+        };
+        let start = 14 + dib_size;
+        for index in 0..colors {
+            let entry = start + index * 4;
+            match bytes.get(entry..entry + 3) {
+                Some(bgr) => palette.push(luminance(bgr[2], bgr[1], bgr[0])),
+                None => return format_err("bitmap palette is truncated"),
+            }
+        }
+    }
+
+    let stride = (bits as usize * width as usize).div_ceil(32) * 4;
+    if bytes.len() < data_offset + stride * height as usize {
+        return format_err("image data is truncated");
+    }
+    let mut pixels = Vec::with_capacity(width as usize * height as usize);
+    for y in 0..height as usize {
+        let row = if top_down { y } else { height as usize - 1 - y };
+        let row = &bytes[data_offset + row * stride..data_offset + (row + 1) * stride];
+        for x in 0..width as usize {
+            let pixel = match bits {
+                1 => palette
+                    .get(((row[x / 8] >> (7 - (x % 8))) & 1) as usize)
+                    .cloned(),
+                8 => palette.get(row[x] as usize).cloned(),
+                24 => Some(luminance(row[x * 3 + 2], row[x * 3 + 1], row[x * 3])),
+                _ => Some(luminance(row[x * 4 + 2], row[x * 4 + 1], row[x * 4])),
+            };
+            pixels.push(pixel.unwrap_or(255));
+        }
+    }
+    Ok(Bitmap {
+        width,
+        height,
+        pixels,
+    })
+}
+
+/// Parses a PBM, PGM or BMP image
+pub fn parse_image(bytes: &[u8]) -> Result<Bitmap, ImportError> {
+    match bytes.get(0..2) {
+        Some(b"P1") | Some(b"P2") | Some(b"P4") | Some(b"P5") => parse_netpbm(bytes),
+        Some(b"BM") => parse_bmp(bytes),
+        _ => format_err("unsupported image format (expected PBM, PGM or BMP)"),
+    }
+}
+
+/// Reads a PBM, PGM or BMP image file
+pub fn read_image<P: AsRef<Path>>(path: P) -> Result<Bitmap, ImportError> {
+    let mut bytes = Vec::new();
+    File::open(path)?.read_to_end(&mut bytes)?;
+    parse_image(&bytes)
+}
+
+/// Creates a puzzle from an image, shading pixels darker than the threshold.
+///
+/// When `minimize` is set, clues are removed as long as the solution stays unique. If the
+/// picture has no unique solution even with every clue shown, every clue is kept.
+pub fn import_image<P: AsRef<Path>>(
+    path: P,
+    threshold: u8,
+    minimize: bool,
+) -> Result<PictureGrid, ImportError> {
+    let bitmap = read_image(path)?;
+    let picture = bitmap.threshold(threshold);
+    if minimize {
+        let generator = Generator::new(bitmap.width, bitmap.height, 0)
+            .with_difficulty(Difficulty::Hard)
+            .with_picture(picture.clone());
+        if let Some(picgrid) = generator.generate() {
+            return Ok(picgrid);
+        }
+    }
+    Ok(clues_from_picture(bitmap.width, bitmap.height, &picture))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns the message of a format error
+    fn format_message(result: Result<Bitmap, ImportError>) -> String {
+        match result {
+            Err(ImportError::Format(message)) => message,
+            Err(err) => panic!("unexpected error {}", err),
+            Ok(_bitmap) => panic!("image was accepted"),
+        }
+    }
+
+    /// Builds a BMP file (bottom-up unless the height is negative) from rows of pixel data
+    fn bmp(
+        width: i32,
+        height: i32,
+        bits: u16,
+        used: u32,
+        palette: &[[u8; 3]],
+        rows: &[&[u8]],
+    ) -> Vec<u8> {
+        let stride = (bits as usize * width as usize).div_ceil(32) * 4;
+        let data_offset = 54 + palette.len() * 4;
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(b"BM");
+        bytes.extend_from_slice(&((data_offset + stride * rows.len()) as u32).to_le_bytes());
+        bytes.extend_from_slice(&[0; 4]);
+        bytes.extend_from_slice(&(data_offset as u32).to_le_bytes());
+        bytes.extend_from_slice(&40u32.to_le_bytes());
+        bytes.extend_from_slice(&width.to_le_bytes());
+        bytes.extend_from_slice(&height.to_le_bytes());
+        bytes.extend_from_slice(&1u16.to_le_bytes());
+        bytes.extend_from_slice(&bits.to_le_bytes());
+        bytes.extend_from_slice(&[0; 16]);
+        bytes.extend_from_slice(&used.to_le_bytes());
+        bytes.extend_from_slice(&[0; 4]);
+        for bgr in palette {
+            bytes.extend_from_slice(bgr);
+            bytes.push(0);
+        }
+        for row in rows {
+            let mut row = row.to_vec();
+            row.resize(stride, 0);
+            bytes.extend_from_slice(&row);
+        }
+        bytes
+    }
+
+    #[test]
+    fn parses_plain_pbm() {
+        let bitmap = parse_image(b"P1\n# comment\n3 2\n1 0 1\n010\n").unwrap();
+        assert_eq!((bitmap.width, bitmap.height), (3, 2));
+        assert_eq!(bitmap.pixels, vec![0, 255, 0, 255, 0, 255]);
+        assert_eq!(
+            bitmap.threshold(128),
+            vec![true, false, true, false, true, false]
+        );
+    }
+
+    #[test]
+    fn parses_raw_pbm() {
+        let mut bytes = b"P4\n3 2\n".to_vec();
+        bytes.extend_from_slice(&[0b1010_0000, 0b0100_0000]);
+        let bitmap = parse_image(&bytes).unwrap();
+        assert_eq!(bitmap.pixels, vec![0, 255, 0, 255, 0, 255]);
+    }
+
+    #[test]
+    fn parses_plain_and_raw_pgm() {
+        let bitmap = parse_image(b"P2\n3 1\n4\n0 2 9\n").unwrap();
+        assert_eq!(bitmap.pixels, vec![0, 127, 255]);
+
+        let mut bytes = b"P5\n2 1\n255\n".to_vec();
+        bytes.extend_from_slice(&[0, 200]);
+        assert_eq!(parse_image(&bytes).unwrap().pixels, vec![0, 200]);
+
+        let mut bytes = b"P5\n2 1\n65535\n".to_vec();
+        bytes.extend_from_slice(&[0xff, 0xff, 0, 0]);
+        assert_eq!(parse_image(&bytes).unwrap().pixels, vec![255, 0]);
+    }
+
+    #[test]
+    fn rejects_corrupt_netpbm() {
+        assert_eq!(
+            format_message(parse_image(b"P1\n3")),
+            "unexpected end of image header"
+        );
+        assert_eq!(
+            format_message(parse_image(b"P1\nx 2\n")),
+            "invalid number in image header"
+        );
+        assert_eq!(
+            format_message(parse_image(b"P1\n0 2\n")),
+            "unsupported image size 0x2"
+        );
+        assert_eq!(
+            format_message(parse_image(b"P2\n1 1\n0\n0\n")),
+            "invalid maximum gray value"
+        );
+        assert_eq!(
+            format_message(parse_image(b"P1\n2 2\n1 0 1\n")),
+            "image data is truncated"
+        );
+        assert_eq!(
+            format_message(parse_image(b"P4\n9 2\n\xff\xff")),
+            "image data is truncated"
+        );
+        assert_eq!(
+            format_message(parse_image(b"P5\n2 2\n255\n\x00")),
+            "image data is truncated"
+        );
+        assert_eq!(
+            format_message(parse_image(b"P3\n1 1\n255\n0 0 0\n")),
+            "unsupported image format (expected PBM, PGM or BMP)"
+        );
+    }
+
+    #[test]
+    fn parses_24_bit_bmp_bottom_up() {
+        let bytes = bmp(
+            2,
+            2,
+            24,
+            0,
+            &[],
+            &[&[0, 0, 0, 255, 255, 255], &[0, 0, 255, 0, 255, 0]],
+        );
+        let bitmap = parse_image(&bytes).unwrap();
+        assert_eq!((bitmap.width, bitmap.height), (2, 2));
+        // the first row in the file is the bottom row
+        assert_eq!(bitmap.pixels, vec![76, 149, 0, 255]);
+    }
+
+    #[test]
+    fn parses_paletted_bmp() {
+        let palette = [[255, 255, 255], [0, 0, 0]];
+        let bytes = bmp(3, 1, 8, 2, &palette, &[&[1, 0, 1]]);
+        assert_eq!(parse_image(&bytes).unwrap().pixels, vec![0, 255, 0]);
+
+        // top-down 1 bit rows
+        let bytes = bmp(3, -2, 1, 2, &palette, &[&[0b1000_0000], &[0b0110_0000]]);
+        let bitmap = parse_image(&bytes).unwrap();
+        assert_eq!(bitmap.pixels, vec![0, 255, 255, 255, 0, 0]);
+    }
+
+    #[test]
+    fn caps_bmp_palette_at_bit_depth() {
+        let palette = [[255, 255, 255], [0, 0, 0]];
+        let bytes = bmp(2, 1, 1, u32::MAX, &palette, &[&[0b0100_0000]]);
+        assert_eq!(parse_image(&bytes).unwrap().pixels, vec![255, 0]);
+    }
+
+    #[test]
+    fn rejects_corrupt_bmp() {
+        assert_eq!(
+            format_message(parse_image(b"BM\x00\x00")),
+            "bitmap header is truncated"
+        );
+
+        let mut bytes = bmp(2, 2, 24, 0, &[], &[&[0; 6], &[0; 6]]);
+        bytes.truncate(bytes.len() - 1);
+        assert_eq!(
+            format_message(parse_image(&bytes)),
+            "image data is truncated"
+        );
+
+        let bytes = bmp(2, 1, 8, 2, &[[0, 0, 0]], &[]);
+        assert_eq!(
+            format_message(parse_image(&bytes[..58])),
+            "bitmap palette is truncated"
+        );
+
+        let mut bytes = bmp(1, 1, 24, 0, &[], &[&[0; 3]]);
+        bytes[30] = 1;
+        assert_eq!(
+            format_message(parse_image(&bytes)),
+            "compressed bitmaps are not supported"
+        );
+
+        let bytes = bmp(1, 1, 16, 0, &[], &[&[0; 2]]);
+        assert_eq!(
+            format_message(parse_image(&bytes)),
+            "only 1, 8, 24 and 32 bit bitmaps are supported"
+        );
+
+        let bytes = bmp(0, 1, 24, 0, &[], &[]);
+        assert_eq!(
+            format_message(parse_image(&bytes)),
+            "unsupported image size 0x1"
+        );
+    }
+}
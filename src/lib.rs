@@ -0,0 +1,47 @@
+#![deny(missing_docs)]
+
+//! Fill-a-pix puzzle model and solver
+
+#[macro_use]
+extern crate serde_derive;
+extern crate png;
+extern crate serde;
+extern crate serde_json;
+
+#[cfg(feature = "gui")]
+extern crate piston_window;
+
+pub use export::{ImageFormat, PictureExport};
+pub use generator::{Difficulty, Generator};
+pub use history::{CellChange, EditHistory};
+pub use importer::{import_image, ImportError};
+pub use loader::{load_file, LoadError, SchemaError};
+pub use picgrid::{CellState, ClueViolation, PictureGrid, SaveMetadata, SizeError, ViolationKind};
+#[cfg(feature = "gui")]
+pub use picgrid_controller::PictureGridController;
+#[cfg(feature = "gui")]
+pub use picgrid_view::PictureGridView;
+pub use sheet::{PuzzleSheet, SheetFormat};
+pub use solver::{
+    count_solutions, find_deduction, Deduction, SolutionCount, SolveReport, Solver, Sweep,
+    Technique,
+};
+pub use text_format::{parse_text, to_text};
+pub use theme::{Theme, ThemeSettings};
+pub use view_settings::PictureGridViewSettings;
+
+pub mod export;
+pub mod generator;
+pub mod history;
+pub mod importer;
+pub mod loader;
+pub mod picgrid;
+#[cfg(feature = "gui")]
+pub mod picgrid_controller;
+#[cfg(feature = "gui")]
+pub mod picgrid_view;
+pub mod sheet;
+pub mod solver;
+pub mod text_format;
+pub mod theme;
+pub mod view_settings;
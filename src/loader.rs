@@ -0,0 +1,613 @@
+//! Puzzle file loading.
+
+use std::convert::TryFrom;
+use std::error::Error;
+use std::fmt;
+use std::fs::File;
+use std::io;
+use std::io::Read;
+use std::path::Path;
+
+use serde::de::{Deserialize, Deserializer, MapAccess, Visitor};
+use serde_json;
+use serde_json::error::Category;
+
+use picgrid::{solution_from_text, CellState, PictureGrid, SaveMetadata, SizeError};
+use text_format::parse_text;
+
+/// Enumeration of schema problems in a puzzle file
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SchemaError {
+    /// The declared width and height are not a supported grid size
+    Size(SizeError),
+    /// The number of cells does not match width * height
+    CellCountMismatch {
+        /// Declared width
+        width: u16,
+        /// Declared height
+        height: u16,
+        /// Number of cells in the file
+        cells: usize,
+    },
+    /// A cell index is outside of width * height
+    IndexOutOfRange {
+        /// Index of the cell
+        index: isize,
+    },
+    /// A cell index appears more than once
+    DuplicateCell {
+        /// Index of the cell
+        index: isize,
+        /// Column of the cell
+        x: isize,
+        /// Row of the cell
+        y: isize,
+    },
+    /// A cell index within width * height is missing
+    MissingCell {
+        /// Index of the cell
+        index: isize,
+        /// Column of the cell
+        x: isize,
+        /// Row of the cell
+        y: isize,
+    },
+    /// A hint is above 9 but is not `PictureGrid::EMPTY`
+    InvalidHint {
+        /// Index of the cell
+        index: isize,
+        /// Column of the cell
+        x: isize,
+        /// Row of the cell
+        y: isize,
+        /// Hint value of the cell
+        hint: u8,
+    },
+    /// The solution does not have one `#` or `.` per cell
+    SolutionLength {
+        /// Declared width
+        width: u16,
+        /// Declared height
+        height: u16,
+        /// Number of cells in the solution
+        cells: usize,
+    },
+    /// The solution has a character other than `#` and `.` (or whitespace)
+    InvalidSolution {
+        /// Index of the cell
+        index: usize,
+        /// Character found
+        found: char,
+    },
+}
+
+impl fmt::Display for SchemaError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SchemaError::Size(err) => write!(f, "{}", err),
+            SchemaError::CellCountMismatch {
+                width,
+                height,
+                cells,
+            } => write!(
+                f,
+                "{}x{} grid needs {} cells, found {}",
+                width,
+                height,
+                *width as usize * *height as usize,
+                cells
+            ),
+            SchemaError::IndexOutOfRange { index } => {
+                write!(f, "cell {} is outside of the grid", index)
+            }
+            SchemaError::DuplicateCell { index, x, y } => {
+                write!(f, "cell {} ({},{}) appears more than once", index, x, y)
+            }
+            SchemaError::MissingCell { index, x, y } => {
+                write!(f, "cell {} ({},{}) is missing", index, x, y)
+            }
+            SchemaError::InvalidHint { index, x, y, hint } => write!(
+                f,
+                "cell {} ({},{}) has hint {} (must be 0-9 or {})",
+                index,
+                x,
+                y,
+                hint,
+                PictureGrid::EMPTY
+            ),
+            SchemaError::SolutionLength {
+                width,
+                height,
+                cells,
+            } => write!(
+                f,
+                "solution of a {}x{} grid needs {} cells, found {}",
+                width,
+                height,
+                *width as usize * *height as usize,
+                cells
+            ),
+            SchemaError::InvalidSolution { index, found } => write!(
+                f,
+                "solution cell {} is '{}' (must be '#' or '.')",
+                index, found
+            ),
+        }
+    }
+}
+
+/// Enumeration of puzzle loading errors
+#[derive(Debug)]
+pub enum LoadError {
+    /// The file could not be read
+    Io(io::Error),
+    /// The file is not valid JSON
+    Syntax {
+        /// Line of the error (starting at 1)
+        line: usize,
+        /// Column of the error (starting at 1)
+        column: usize,
+        /// Description of the error
+        message: String,
+    },
+    /// The file is valid JSON, but does not have the structure of a puzzle
+    Structure {
+        /// Line of the error (starting at 1)
+        line: usize,
+        /// Column of the error (starting at 1)
+        column: usize,
+        /// Description of the error
+        message: String,
+    },
+    /// The puzzle does not match its declared dimensions or hint range (every problem found)
+    Schema(Vec<SchemaError>),
+    /// The file is not a valid text format puzzle
+    Text {
+        /// Line of the error (starting at 1)
+        line: usize,
+        /// Column of the error (starting at 1)
+        column: usize,
+        /// Description of the error
+        message: String,
+    },
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LoadError::Io(err) => write!(f, "{}", err),
+            LoadError::Syntax {
+                line,
+                column,
+                message,
+            } => write!(
+                f,
+                "JSON syntax error at line {} column {}: {}",
+                line, column, message
+            ),
+            LoadError::Structure {
+                line,
+                column,
+                message,
+            } => write!(
+                f,
+                "not a puzzle at line {} column {}: {}",
+                line, column, message
+            ),
+            LoadError::Schema(errors) => {
+                let messages: Vec<String> = errors.iter().map(|err| err.to_string()).collect();
+                if messages.len() == 1 {
+                    write!(f, "{}", messages[0])
+                } else {
+                    write!(f, "{} problems: {}", messages.len(), messages.join("; "))
+                }
+            }
+            LoadError::Text {
+                line,
+                column,
+                message,
+            } => write!(
+                f,
+                "text puzzle error at line {} column {}: {}",
+                line, column, message
+            ),
+        }
+    }
+}
+
+impl Error for LoadError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            LoadError::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for LoadError {
+    fn from(err: io::Error) -> LoadError {
+        LoadError::Io(err)
+    }
+}
+
+impl From<serde_json::Error> for LoadError {
+    fn from(err: serde_json::Error) -> LoadError {
+        let (line, column) = (err.line(), err.column());
+        // drop the position serde_json appends, it is kept separately
+        let message = err.to_string();
+        let message = match message.rfind(" at line ") {
+            Some(pos) => message[..pos].to_string(),
+            None => message,
+        };
+        match err.classify() {
+            Category::Io => LoadError::Io(err.into()),
+            Category::Syntax | Category::Eof => LoadError::Syntax {
+                line,
+                column,
+                message,
+            },
+            Category::Data => LoadError::Structure {
+                line,
+                column,
+                message,
+            },
+        }
+    }
+}
+
+/// Cells as they appear in the file, keeping duplicate indices.
+struct RawCells(Vec<(isize, CellState)>);
+
+impl<'de> Deserialize<'de> for RawCells {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<RawCells, D::Error> {
+        struct RawCellsVisitor;
+
+        impl<'de> Visitor<'de> for RawCellsVisitor {
+            type Value = RawCells;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "a map of cell indices to cell states")
+            }
+
+            fn visit_map<M: MapAccess<'de>>(self, mut map: M) -> Result<RawCells, M::Error> {
+                let mut cells = Vec::with_capacity(map.size_hint().unwrap_or(0));
+                while let Some(entry) = map.next_entry()? {
+                    cells.push(entry);
+                }
+                Ok(RawCells(cells))
+            }
+        }
+
+        deserializer.deserialize_map(RawCellsVisitor)
+    }
+}
+
+/// Picture grid as it appears in the file.
+#[derive(Deserialize)]
+pub(crate) struct RawPictureGrid {
+    width: u16,
+    height: u16,
+    cells: RawCells,
+    #[serde(default)]
+    metadata: Option<SaveMetadata>,
+    #[serde(default)]
+    solution: Option<String>,
+}
+
+impl RawPictureGrid {
+    /// Checks the cells against the declared dimensions and hint range.
+    ///
+    /// Every problem is reported, not just the first.
+    fn into_picgrid(self) -> Result<PictureGrid, Vec<SchemaError>> {
+        // the cells of an unsupported size are not checked (they may not fit in memory)
+        PictureGrid::check_size(self.width, self.height)
+            .map_err(|err| vec![SchemaError::Size(err)])?;
+        let width = self.width as isize;
+        let count = self.width as usize * self.height as usize;
+        let position = |index: isize| (index % width, index / width);
+        let mut errors = Vec::new();
+        if self.cells.0.len() != count {
+            errors.push(SchemaError::CellCountMismatch {
+                width: self.width,
+                height: self.height,
+                cells: self.cells.0.len(),
+            });
+        }
+
+        let mut cells: Vec<Option<CellState>> = vec![None; count];
+        for (index, state) in self.cells.0 {
+            let (x, y) = position(index);
+            if index < 0 || index as usize >= count {
+                errors.push(SchemaError::IndexOutOfRange { index });
+                continue;
+            }
+            if state.hint() > 9 && state.hint() != PictureGrid::EMPTY {
+                errors.push(SchemaError::InvalidHint {
+                    index,
+                    x,
+                    y,
+                    hint: state.hint(),
+                });
+            }
+            if cells[index as usize].replace(state).is_some() {
+                errors.push(SchemaError::DuplicateCell { index, x, y });
+            }
+        }
+        let solution = match self.solution {
+            Some(ref text) => match solution_from_text(text) {
+                Ok(solution) if solution.len() == count => Some(solution),
+                Ok(solution) => {
+                    errors.push(SchemaError::SolutionLength {
+                        width: self.width,
+                        height: self.height,
+                        cells: solution.len(),
+                    });
+                    None
+                }
+                Err((index, found)) => {
+                    errors.push(SchemaError::InvalidSolution { index, found });
+                    None
+                }
+            },
+            None => None,
+        };
+        for (index, cell) in cells.iter().enumerate() {
+            if cell.is_none() {
+                let (x, y) = position(index as isize);
+                errors.push(SchemaError::MissingCell {
+                    index: index as isize,
+                    x,
+                    y,
+                });
+            }
+        }
+
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+        let mut picgrid = PictureGrid::new(self.width, self.height);
+        picgrid.with_values(cells.into_iter().flatten().collect());
+        picgrid.metadata = self.metadata;
+        picgrid.solution = solution;
+        Ok(picgrid)
+    }
+}
+
+impl TryFrom<RawPictureGrid> for PictureGrid {
+    type Error = LoadError;
+
+    /// Checks the cells (see `RawPictureGrid::into_picgrid`), for deserializing a `PictureGrid`
+    fn try_from(raw: RawPictureGrid) -> Result<PictureGrid, LoadError> {
+        raw.into_picgrid().map_err(LoadError::Schema)
+    }
+}
+
+/// Loads a puzzle from JSON text
+pub fn load_json(json: &str) -> Result<PictureGrid, LoadError> {
+    let raw: RawPictureGrid = serde_json::from_str(json)?;
+    raw.into_picgrid().map_err(LoadError::Schema)
+}
+
+/// Loads a puzzle from JSON or the compact text format (JSON starts with `{`)
+pub fn load_str(text: &str) -> Result<PictureGrid, LoadError> {
+    if text.trim_start().starts_with('{') {
+        load_json(text)
+    } else {
+        parse_text(text)
+    }
+}
+
+/// Loads a puzzle from a JSON or text format file
+pub fn load_file<P: AsRef<Path>>(path: P) -> Result<PictureGrid, LoadError> {
+    let mut text = String::new();
+    File::open(path)?.read_to_string(&mut text)?;
+    load_str(&text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns the error of a puzzle that fails to load
+    fn load_err(json: &str) -> LoadError {
+        match load_json(json) {
+            Err(err) => err,
+            Ok(_picgrid) => panic!("puzzle was loaded"),
+        }
+    }
+
+    #[test]
+    fn malformed_json_is_syntax_error() {
+        match load_err("{\n  \"width\": 1,\n  \"height\": 1,,\n}") {
+            LoadError::Syntax { line, column, .. } => assert_eq!((line, column), (3, 15)),
+            other => panic!("unexpected error {:?}", other),
+        }
+        match load_err("{\"width\": 1") {
+            LoadError::Syntax { line, column, .. } => assert_eq!((line, column), (1, 11)),
+            other => panic!("unexpected error {:?}", other),
+        }
+    }
+
+    #[test]
+    fn wrong_type_is_structure_error() {
+        let err = load_err("{\n  \"width\": \"five\",\n  \"height\": 1,\n  \"cells\": {}\n}");
+        match err {
+            LoadError::Structure { line, column, .. } => assert_eq!((line, column), (2, 17)),
+            ref other => panic!("unexpected error {:?}", other),
+        }
+        // the position is only shown once
+        assert!(err
+            .to_string()
+            .starts_with("not a puzzle at line 2 column 17: invalid type"));
+        assert!(!err.to_string().contains(" at line 2 column 17 "));
+
+        let err = load_err(r#"{"width": 1, "height": 1, "cells": {"0": {"Painted": 1}}}"#);
+        assert!(matches!(err, LoadError::Structure { line: 1, .. }));
+        let err = load_err(r#"{"width": 1, "cells": {}}"#);
+        assert!(matches!(err, LoadError::Structure { .. }));
+    }
+
+    #[test]
+    fn invalid_hint_is_schema_error() {
+        let err = load_err(
+            r#"{"width": 2, "height": 1, "cells": {"0": {"Unsolved": 12}, "1": {"Unsolved": 10}}}"#,
+        );
+        match err {
+            LoadError::Schema(ref errors) => assert_eq!(
+                errors,
+                &vec![SchemaError::InvalidHint {
+                    index: 0,
+                    x: 0,
+                    y: 0,
+                    hint: 12
+                }]
+            ),
+            ref other => panic!("unexpected error {:?}", other),
+        }
+        assert_eq!(
+            err.to_string(),
+            "cell 0 (0,0) has hint 12 (must be 0-9 or 10)"
+        );
+    }
+
+    #[test]
+    fn missing_file_is_io_error() {
+        match load_file("test_files/does-not-exist.json") {
+            Err(LoadError::Io(_err)) => {}
+            Err(other) => panic!("unexpected error {:?}", other),
+            Ok(_picgrid) => panic!("puzzle was loaded"),
+        }
+    }
+
+    #[test]
+    fn loads_valid_puzzle() {
+        let picgrid =
+            load_file("test_files/original-5x5.json").unwrap_or_else(|err| panic!("{}", err));
+        assert_eq!((picgrid.width, picgrid.height), (5, 5));
+        assert_eq!(picgrid.get(0, 0), Some(CellState::Unsolved(0)));
+        assert_eq!(
+            picgrid.get(1, 0),
+            Some(CellState::Unsolved(PictureGrid::EMPTY))
+        );
+    }
+
+    #[test]
+    fn every_schema_problem_is_reported() {
+        // cell 1 appears twice (JSON allows duplicate keys), cell 2 is missing
+        let err = load_err(
+            r#"{"width": 3, "height": 1, "cells": {
+                "0": {"Unsolved": 11},
+                "1": {"Shaded": 1},
+                "1": {"Unshaded": 2},
+                "5": {"Unsolved": 0}
+            }}"#,
+        );
+        let errors = match err {
+            LoadError::Schema(ref errors) => errors.clone(),
+            ref other => panic!("unexpected error {:?}", other),
+        };
+        assert_eq!(
+            errors,
+            vec![
+                SchemaError::CellCountMismatch {
+                    width: 3,
+                    height: 1,
+                    cells: 4
+                },
+                SchemaError::InvalidHint {
+                    index: 0,
+                    x: 0,
+                    y: 0,
+                    hint: 11
+                },
+                SchemaError::DuplicateCell {
+                    index: 1,
+                    x: 1,
+                    y: 0
+                },
+                SchemaError::IndexOutOfRange { index: 5 },
+                SchemaError::MissingCell {
+                    index: 2,
+                    x: 2,
+                    y: 0
+                },
+            ]
+        );
+        assert!(err
+            .to_string()
+            .starts_with("5 problems: 3x1 grid needs 3 cells, found 4; "));
+    }
+
+    #[test]
+    fn missing_cells_are_reported_with_position() {
+        let err = load_err(
+            r#"{"width": 2, "height": 2, "cells": {"0": {"Unsolved": 1}, "1": {"Unsolved": 1}}}"#,
+        );
+        match err {
+            LoadError::Schema(ref errors) => assert_eq!(
+                errors[1..].to_vec(),
+                vec![
+                    SchemaError::MissingCell {
+                        index: 2,
+                        x: 0,
+                        y: 1
+                    },
+                    SchemaError::MissingCell {
+                        index: 3,
+                        x: 1,
+                        y: 1
+                    },
+                ]
+            ),
+            ref other => panic!("unexpected error {:?}", other),
+        }
+    }
+
+    #[test]
+    fn solution_is_loaded() {
+        let picgrid = load_json(
+            r##"{"width": 2, "height": 1, "cells": {"0": {"Unsolved": 1}, "1": {"Unsolved": 1}},
+                 "solution": "# ."}"##,
+        )
+        .unwrap_or_else(|err| panic!("{}", err));
+        assert_eq!(picgrid.solution, Some(vec![true, false]));
+    }
+
+    #[test]
+    fn bad_solution_is_schema_error() {
+        let cells = r#""cells": {"0": {"Unsolved": 1}, "1": {"Unsolved": 1}}"#;
+        let errors = |solution: &str| {
+            let json = format!(
+                r#"{{"width": 2, "height": 1, {}, "solution": "{}"}}"#,
+                cells, solution
+            );
+            match load_err(&json) {
+                LoadError::Schema(errors) => errors,
+                other => panic!("unexpected error {:?}", other),
+            }
+        };
+        assert_eq!(
+            errors("#.#"),
+            vec![SchemaError::SolutionLength {
+                width: 2,
+                height: 1,
+                cells: 3
+            }]
+        );
+        assert_eq!(
+            errors(". o"),
+            vec![SchemaError::InvalidSolution {
+                index: 1,
+                found: 'o'
+            }]
+        );
+        assert_eq!(
+            SchemaError::InvalidSolution {
+                index: 1,
+                found: 'o'
+            }
+            .to_string(),
+            "solution cell 1 is 'o' (must be '#' or '.')"
+        );
+    }
+}
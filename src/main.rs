@@ -1,76 +1,211 @@
 #![deny(missing_docs)]
 
 //! A Fill-a-pix viewer
-
-#[macro_use]
-extern crate serde_derive;
-extern crate serde;
+//!
+//! Usage:
+//!
+//! - `fill-a-pix-rust <puzzle.json> [--save-on-exit] [--strict] [--theme <name>]
+//!   [--settings <file>]` opens the puzzle in a window
+//! - `fill-a-pix-rust solve <puzzle.json> [--output <solved.json>] [--sweep-only]` solves
+//!   without a window
+//! - `fill-a-pix-rust unique <puzzle.json> [--limit <count>]` reports if the solution is unique
+//! - `fill-a-pix-rust generate <width> <height> [--seed <number>] [--difficulty <level>]
+//!   [--picture <solved.json>] [--output <puzzle.json>]` generates a unique-solution puzzle
+//!   no harder than the level (`hard` is unique, any difficulty)
+//! - `fill-a-pix-rust import <image> [--threshold <0-255>] [--minimize] [--output <puzzle.json>]`
+//!   creates a puzzle from a PBM, PGM or BMP image
+//! - `fill-a-pix-rust check <puzzle.json or directory>...` lists every problem in puzzle files
+//! - `fill-a-pix-rust convert <input> <output.json or output.txt>` converts between JSON and
+//!   the compact text format
+//! - `fill-a-pix-rust export <puzzle.json> <output.png or output.svg> [--clues]
+//!   [--cell-size <pixels>]` exports the solved picture (optionally with the clues) as an image
+//! - `fill-a-pix-rust sheet <puzzle.json> <output.pdf or output.svg> [--answers]
+//!   [--page a4|letter] [--margin <points>] [--cell-size <points>]` writes a printable sheet
+//!
+//! Puzzles can be given in JSON or the compact text format (detected from the contents).
+
+extern crate fill_a_pix_rust;
 extern crate serde_json;
 
+#[cfg(feature = "gui")]
 extern crate find_folder;
+#[cfg(feature = "gui")]
 extern crate piston_window;
 
-use std::fs::File;
-use std::io;
-
-use serde_json::error;
+#[cfg(feature = "gui")]
+use std::path::Path;
 
-use piston_window::types::Color;
+#[cfg(feature = "gui")]
 use piston_window::{clear, Filter, Glyphs, PistonWindow, TextureSettings, WindowSettings};
 
-pub use picgrid::{CellState, PictureGrid};
-pub use picgrid_controller::PictureGridController;
-pub use picgrid_view::{PictureGridView, PictureGridViewSettings};
-
-mod picgrid;
-mod picgrid_controller;
-mod picgrid_view;
+use fill_a_pix_rust::{load_file, PictureGrid};
+#[cfg(feature = "gui")]
+use fill_a_pix_rust::{
+    PictureGridController, PictureGridView, PictureGridViewSettings, ThemeSettings,
+};
 
-const BGCOLOR: Color = [0.89, 0.87, 0.73, 1.0];
+#[cfg(feature = "gui")]
 const DEFAULT_WINDOW: [u32; 2] = [1440, 900];
+#[cfg(feature = "gui")]
+const DEFAULT_SETTINGS_FILE: &str = "fill-a-pix.json";
+
+mod commands;
+
+/// Options of the puzzle window
+struct WindowOptions {
+    /// Saves the game when the window closes
+    save_on_exit: bool,
+    /// Marks wrong cells as soon as they are placed
+    strict: bool,
+    /// Name of the theme to start with (overrides the settings file)
+    theme: Option<String>,
+    /// Path of the settings file (`fill-a-pix.json` in the current directory if it exists)
+    settings: Option<String>,
+}
 
-fn load_file(filename: &String) -> Result<PictureGrid, i32> {
-    println!("Loading {}...", filename);
+impl WindowOptions {
+    /// Parses the options following the puzzle file name
+    fn parse(args: &[String]) -> WindowOptions {
+        let mut options = WindowOptions {
+            save_on_exit: false,
+            strict: false,
+            theme: None,
+            settings: None,
+        };
+        let mut iter = args.iter();
+        while let Some(arg) = iter.next() {
+            match arg.as_str() {
+                "--save-on-exit" => options.save_on_exit = true,
+                "--strict" => options.strict = true,
+                "--theme" => options.theme = iter.next().cloned(),
+                "--settings" => options.settings = iter.next().cloned(),
+                _ => {}
+            }
+        }
+        options
+    }
+}
 
-    let io_err = |err: io::Error| -> i32 {
-        if let Some(raw_os_err) = err.raw_os_error() {
-            raw_os_err
-        } else {
-            1
+/// Loads a puzzle, exiting the process if it cannot be loaded
+fn load_or_exit(filename: &str) -> PictureGrid {
+    eprintln!("Loading {}...", filename);
+    match load_file(filename) {
+        Ok(pg) => {
+            eprintln!("{} loaded!", filename);
+            pg
         }
-    };
-    let input = File::open(filename).map_err(io_err)?;
-
-    let serde_err = |err: error::Error| -> i32 {
-        match err.classify() {
-            error::Category::Io => 1001,
-            error::Category::Syntax => 1002,
-            error::Category::Data => 1003,
-            error::Category::Eof => 1004,
+        Err(err) => {
+            let error_code = 1;
+            eprintln!(
+                "Error({}): Unable to load {}: {}",
+                error_code, filename, err
+            );
+            std::process::exit(error_code);
         }
-    };
-    let picgrid = serde_json::from_reader(input).map_err(serde_err)?;
-    println!("{} loaded!", filename);
-    Ok(picgrid)
+    }
 }
 
 fn main() {
-    let picgrid: PictureGrid;
-    if let Some(filename) = std::env::args().nth(1) {
-        picgrid = match load_file(&filename) {
-            Ok(pg) => pg,
-            Err(error_code) => {
-                println!("Error({}): Unable to load {}", error_code, filename);
-                std::process::exit(error_code);
-            }
-        };
-    } else {
-        let error_code = 1;
-        println!("Error({}): No filename provided", error_code);
-        std::process::exit(error_code);
+    let args: Vec<String> = std::env::args().collect();
+    let exit_code = match args.get(1).map(|arg| arg.as_str()) {
+        Some("solve") => commands::solve(&args[2..]),
+        Some("unique") => commands::unique(&args[2..]),
+        Some("generate") => commands::generate(&args[2..]),
+        Some("import") => commands::import(&args[2..]),
+        Some("check") => commands::check(&args[2..]),
+        Some("convert") => commands::convert(&args[2..]),
+        Some("export") => commands::export(&args[2..]),
+        Some("sheet") => commands::sheet(&args[2..]),
+        Some(filename) => {
+            let picgrid = load_or_exit(filename);
+            run_window(filename, picgrid, &WindowOptions::parse(&args[2..]));
+            0
+        }
+        None => {
+            let error_code = 1;
+            eprintln!("Error({}): No filename provided", error_code);
+            error_code
+        }
+    };
+    std::process::exit(exit_code);
+}
+
+#[cfg(not(feature = "gui"))]
+fn run_window(_filename: &str, _picgrid: PictureGrid, _options: &WindowOptions) {
+    let error_code = 1;
+    eprintln!("Error({}): Built without the \"gui\" feature", error_code);
+    std::process::exit(error_code);
+}
+
+/// Loads the settings file, exiting the process if it exists but cannot be loaded
+#[cfg(feature = "gui")]
+fn load_settings_or_exit(options: &WindowOptions) -> ThemeSettings {
+    let path = match options.settings {
+        Some(ref path) => path.as_str(),
+        None if Path::new(DEFAULT_SETTINGS_FILE).exists() => DEFAULT_SETTINGS_FILE,
+        None => return ThemeSettings::default(),
+    };
+    match ThemeSettings::load(path) {
+        Ok(settings) => settings,
+        Err(err) => {
+            let error_code = 1;
+            eprintln!(
+                "Error({}): Unable to load settings {}: {}",
+                error_code, path, err
+            );
+            std::process::exit(error_code);
+        }
     }
+}
 
+#[cfg(feature = "gui")]
+fn run_window(filename: &str, picgrid: PictureGrid, options: &WindowOptions) {
+    // saved games are saved in place, puzzles next to the original file
+    let save_path = if picgrid.metadata.is_some() {
+        filename.to_string()
+    } else {
+        Path::new(filename)
+            .with_extension("save.json")
+            .to_string_lossy()
+            .into_owned()
+    };
     let mut picgrid_controller = PictureGridController::new(picgrid);
+    picgrid_controller.save_path = Some(save_path);
+    picgrid_controller.export_path = Some(
+        Path::new(filename)
+            .with_extension("")
+            .to_string_lossy()
+            .into_owned(),
+    );
+    if picgrid_controller.original.is_none() {
+        picgrid_controller.original = Some(filename.to_string());
+    }
+    if options.strict {
+        picgrid_controller.set_strict(true);
+    }
+
+    let settings = load_settings_or_exit(options);
+    picgrid_controller.themes = settings.all_themes();
+    if let Some(name) = options.theme.as_ref().or(settings.theme.as_ref()) {
+        match settings.find_theme(name) {
+            Some(index) => picgrid_controller.theme_index = index,
+            None => {
+                let error_code = 1;
+                let names: Vec<&str> = picgrid_controller
+                    .themes
+                    .iter()
+                    .map(|theme| theme.name.as_str())
+                    .collect();
+                eprintln!(
+                    "Error({}): Unknown theme '{}' (expected {})",
+                    error_code,
+                    name,
+                    names.join(", ")
+                );
+                std::process::exit(error_code);
+            }
+        }
+    }
     let picgrid_view_settings = PictureGridViewSettings::new();
     let mut picgrid_view = PictureGridView::new(picgrid_view_settings);
 
@@ -82,17 +217,34 @@ fn main() {
     let assets = find_folder::Search::ParentsThenKids(3, 3)
         .for_folder("assets")
         .unwrap();
-    let ref font = assets.join("FiraSans-Regular.ttf");
+    let font = &assets.join("FiraSans-Regular.ttf");
     let factory = window.factory.clone();
     let texture_settings = TextureSettings::new().filter(Filter::Nearest);
     let mut glyphs = Glyphs::new(font, factory, texture_settings).unwrap();
 
     while let Some(event) = window.next() {
-        picgrid_controller.event(picgrid_view.grid_rect, picgrid_view.cell_size, &event);
+        picgrid_controller.event(
+            picgrid_view.grid_area,
+            picgrid_view.grid_rect,
+            picgrid_view.cell_size,
+            &event,
+        );
+        // before anything uses the colors, so a theme change shows (and exports) at once
+        if let Some(theme) = picgrid_controller.theme() {
+            theme.apply(&mut picgrid_view.settings);
+        }
+        picgrid_controller.export_if_requested(&picgrid_view.settings);
 
         window.draw_2d(&event, |context, graphics| {
-            clear(BGCOLOR, graphics);
+            clear(picgrid_view.settings.background_color, graphics);
             picgrid_view.draw(&picgrid_controller, &mut glyphs, &context, graphics);
         });
     }
+
+    if options.save_on_exit {
+        match picgrid_controller.save() {
+            Ok(()) => eprintln!("Game saved!"),
+            Err(err) => eprintln!("Error: Unable to save game: {}", err),
+        }
+    }
 }
@@ -1,9 +1,17 @@
 //! Grid for picture.
 
-use std::collections::HashMap;
+use std::fmt;
+use std::fs::File;
+use std::io;
+use std::path::Path;
+
+use serde::Serializer;
+use serde_json;
+
+use loader::RawPictureGrid;
 
 /// Enumeration of cell states
-#[derive(Copy, Clone, Serialize, Deserialize)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub enum CellState {
     /// Cell is not solved yet
     Unsolved(u8),
@@ -24,46 +32,280 @@ impl CellState {
     }
 }
 
+/// Enumeration of ways a clue can be violated
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ViolationKind {
+    /// More neighbors are shaded than the hint
+    TooManyShaded,
+    /// Shaded and unsolved neighbors together are fewer than the hint
+    TooFewShaded,
+}
+
+/// A clue that can no longer be satisfied
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct ClueViolation {
+    /// Column of the clue
+    pub x: isize,
+    /// Row of the clue
+    pub y: isize,
+    /// Hint value of the clue
+    pub hint: u8,
+    /// Number of shaded cells in surrounding grid
+    pub shaded: u8,
+    /// Number of unsolved cells in surrounding grid
+    pub unsolved: u8,
+    /// How the clue is violated
+    pub kind: ViolationKind,
+}
+
+impl fmt::Display for ClueViolation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.kind {
+            ViolationKind::TooManyShaded => write!(
+                f,
+                "({},{}) hint {}: {} shaded",
+                self.x, self.y, self.hint, self.shaded
+            ),
+            ViolationKind::TooFewShaded => write!(
+                f,
+                "({},{}) hint {}: only {} shaded and {} unsolved",
+                self.x, self.y, self.hint, self.shaded, self.unsolved
+            ),
+        }
+    }
+}
+
+/// Stores information about a saved game.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct SaveMetadata {
+    /// Seconds spent playing the puzzle
+    pub elapsed_secs: f64,
+    /// Path of the original puzzle file (if known)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub original: Option<String>,
+    /// Time of the save (seconds since the Unix epoch)
+    #[serde(default)]
+    pub saved_at: u64,
+}
+
+/// Enumeration of unsupported grid sizes
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SizeError {
+    /// The width or height is zero
+    Empty {
+        /// Requested width
+        width: u16,
+        /// Requested height
+        height: u16,
+    },
+    /// The grid has more than `PictureGrid::MAX_CELLS` cells
+    TooLarge {
+        /// Requested width
+        width: u16,
+        /// Requested height
+        height: u16,
+    },
+}
+
+impl fmt::Display for SizeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SizeError::Empty { width, height } => {
+                write!(f, "{}x{} grid has no cells", width, height)
+            }
+            SizeError::TooLarge { width, height } => write!(
+                f,
+                "{}x{} grid has {} cells (at most {} are supported)",
+                width,
+                height,
+                *width as usize * *height as usize,
+                PictureGrid::MAX_CELLS
+            ),
+        }
+    }
+}
+
+/// Writes the cells as a map of cell index to cell state (the JSON file shape).
+fn serialize_cells<S: Serializer>(cells: &[CellState], serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_map(
+        cells
+            .iter()
+            .enumerate()
+            .map(|(index, cell)| (index as isize, cell)),
+    )
+}
+
+/// Writes a known solution as a string of `#` (shaded) and `.` (unshaded), row-major.
+fn serialize_solution<S: Serializer>(
+    solution: &Option<Vec<bool>>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    match solution {
+        Some(solution) => serializer.collect_str(&solution_to_text(solution)),
+        None => serializer.serialize_none(),
+    }
+}
+
+/// Writes a solution (shaded cells are `true`) as `#` and `.` characters
+pub fn solution_to_text(solution: &[bool]) -> String {
+    solution
+        .iter()
+        .map(|shaded| if *shaded { '#' } else { '.' })
+        .collect()
+}
+
+/// Reads a solution from `#` and `.` characters (whitespace is ignored).
+///
+/// Returns the cell index and character of the first other character as the error.
+pub fn solution_from_text(text: &str) -> Result<Vec<bool>, (usize, char)> {
+    text.chars()
+        .filter(|c| !c.is_whitespace())
+        .enumerate()
+        .map(|(index, c)| match c {
+            '#' => Ok(true),
+            '.' => Ok(false),
+            _ => Err((index, c)),
+        })
+        .collect()
+}
+
 /// Stores picture grid data.
-#[derive(Serialize, Deserialize)]
+///
+/// Deserializing checks the cells as `load_json` does (the error lists every problem found).
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(try_from = "RawPictureGrid")]
 pub struct PictureGrid {
     /// Width of grid.
     pub width: u16,
     /// Height of grid.
     pub height: u16,
-    /// Stores the content of the grid cells.
-    pub cells: HashMap<isize, CellState>,
+    /// Stores the content of the grid cells (row-major).
+    #[serde(serialize_with = "serialize_cells")]
+    pub cells: Vec<CellState>,
+    /// Stores information about the saved game (only present in saved games).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<SaveMetadata>,
+    /// Stores the known solution (shaded cells are `true`, row-major), if there is one.
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "serialize_solution"
+    )]
+    pub solution: Option<Vec<bool>>,
 }
 
 impl PictureGrid {
     /// Signifies that cell has no hint.
     pub const EMPTY: u8 = 10;
 
-    /// Creates new picture grid.
+    /// Largest supported number of cells (4096x4096, or 65535x256).
+    pub const MAX_CELLS: usize = 1 << 24;
+
+    /// Checks that a grid of the size has at least one and at most `MAX_CELLS` cells
+    pub fn check_size(width: u16, height: u16) -> Result<(), SizeError> {
+        if width == 0 || height == 0 {
+            Err(SizeError::Empty { width, height })
+        } else if width as usize * height as usize > PictureGrid::MAX_CELLS {
+            Err(SizeError::TooLarge { width, height })
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Creates new picture grid, rejecting unsupported sizes (see `check_size`).
+    pub fn try_new(width: u16, height: u16) -> Result<PictureGrid, SizeError> {
+        PictureGrid::check_size(width, height)?;
+        Ok(PictureGrid::new(width, height))
+    }
+
+    /// Creates new picture grid (the size is not checked, see `try_new`).
     pub fn new(width: u16, height: u16) -> PictureGrid {
         PictureGrid {
-            width: width,
-            height: height,
-            cells: HashMap::with_capacity((width * height) as usize),
-            //vec![CellState::Unsolved(PictureGrid::EMPTY); (width * height) as usize],
+            width,
+            height,
+            cells: vec![CellState::Unsolved(PictureGrid::EMPTY); width as usize * height as usize],
+            metadata: None,
+            solution: None,
         }
     }
 
-    /// Sets all cell values
-    pub fn with_values(&mut self, values: HashMap<isize, CellState>) -> &Self {
+    /// Writes the grid (including any save metadata) to a JSON file
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let file = File::create(path)?;
+        serde_json::to_writer(file, self).map_err(io::Error::from)
+    }
+
+    /// Sets all cell values (row-major)
+    pub fn with_values(&mut self, values: Vec<CellState>) -> &Self {
         self.cells = values;
         self
     }
 
-    /// Get individual cell value
-    pub fn get(&self, x: isize, y: isize) -> Option<CellState> {
-        let mut ret_val = Some(CellState::Unshaded(PictureGrid::EMPTY));
+    /// Finds the index of (x,y) in the cells (None if outside of the grid)
+    fn index(&self, x: isize, y: isize) -> Option<usize> {
         if x >= 0 && x < self.width as isize && y >= 0 && y < self.height as isize {
-            ret_val = Some(self.cells[&(y * self.width as isize + x)].clone());
+            Some(y as usize * self.width as usize + x as usize)
+        } else {
+            None
+        }
+    }
+
+    /// Get individual cell value (cells outside of the grid are unshaded, missing cells are None)
+    pub fn get(&self, x: isize, y: isize) -> Option<CellState> {
+        match self.index(x, y) {
+            Some(index) => self.cells.get(index).cloned(),
+            None => Some(CellState::Unshaded(PictureGrid::EMPTY)),
+        }
+    }
+
+    /// Finds number of unsolved cells in the whole grid
+    pub fn num_unsolved_cells(&self) -> usize {
+        self.cells
+            .iter()
+            .filter(|cell| matches!(cell, CellState::Unsolved(_hint)))
+            .count()
+    }
+
+    /// Finds if every cell in the grid is solved
+    pub fn is_solved(&self) -> bool {
+        self.num_unsolved_cells() == 0
+    }
+
+    /// Finds the cells whose state differs from the other grid (hints are ignored)
+    pub fn differences(&self, other: &PictureGrid) -> Vec<[isize; 2]> {
+        let mut ret_val = Vec::new();
+        for y in 0..self.height as isize {
+            for x in 0..self.width as isize {
+                let same = matches!(
+                    (self.get(x, y), other.get(x, y)),
+                    (Some(CellState::Unsolved(_)), Some(CellState::Unsolved(_)))
+                        | (Some(CellState::Shaded(_)), Some(CellState::Shaded(_)))
+                        | (Some(CellState::Unshaded(_)), Some(CellState::Unshaded(_)))
+                );
+                if !same {
+                    ret_val.push([x, y]);
+                }
+            }
         }
         ret_val
     }
 
+    /// Finds the solved cells whose state differs from a solution (shaded cells are `true`,
+    /// row-major), such as the known solution. Unsolved cells are never wrong.
+    pub fn wrong_cells(&self, solution: &[bool]) -> Vec<[isize; 2]> {
+        let width = self.width as usize;
+        self.cells
+            .iter()
+            .zip(solution.iter())
+            .enumerate()
+            .filter(|(_index, (cell, shaded))| match cell {
+                CellState::Shaded(_hint) => !**shaded,
+                CellState::Unshaded(_hint) => **shaded,
+                CellState::Unsolved(_hint) => false,
+            })
+            .map(|(index, _cell)| [(index % width) as isize, (index / width) as isize])
+            .collect()
+    }
+
     /// Get next incomplete cell, starting at (x,y) (without wrapping around)
     pub fn next_incomplete(&self, x: isize, y: isize) -> (isize, isize, Option<CellState>) {
         let mut next_x = x;
@@ -86,8 +328,8 @@ impl PictureGrid {
 
     /// Set individual cell value
     pub fn set(&mut self, x: isize, y: isize, value: CellState) -> &Self {
-        if x >= 0 && x < self.width as isize && y >= 0 && y < self.height as isize {
-            self.cells.insert(y * self.width as isize + x, value);
+        if let Some(cell) = self.index(x, y).and_then(|index| self.cells.get_mut(index)) {
+            *cell = value;
         }
         self
     }
@@ -100,30 +342,19 @@ impl PictureGrid {
         value: CellState,
         unsolved_only: bool,
     ) -> &Self {
-        if x >= 0 && x < self.width as isize && y >= 0 && y < self.height as isize {
-            let index = y * self.width as isize + x;
-            let mut existing_hint: Option<u8> = None;
-            match self.cells[&index] {
-                CellState::Unsolved(hint) => existing_hint = Some(hint),
-                CellState::Shaded(hint) => {
-                    if !unsolved_only {
-                        existing_hint = Some(hint);
-                    }
-                }
-                CellState::Unshaded(hint) => {
-                    if !unsolved_only {
-                        existing_hint = Some(hint);
-                    }
-                }
-            }
+        if let Some(cell) = self.index(x, y).and_then(|index| self.cells.get_mut(index)) {
+            let existing_hint = match *cell {
+                CellState::Unsolved(hint) => Some(hint),
+                CellState::Shaded(hint) if !unsolved_only => Some(hint),
+                CellState::Unshaded(hint) if !unsolved_only => Some(hint),
+                _ => None,
+            };
             if let Some(hint) = existing_hint {
-                let new_value;
-                match value {
-                    CellState::Unsolved(_hint) => new_value = CellState::Unsolved(hint),
-                    CellState::Shaded(_hint) => new_value = CellState::Shaded(hint),
-                    CellState::Unshaded(_hint) => new_value = CellState::Unshaded(hint),
-                }
-                self.cells.insert(index, new_value);
+                *cell = match value {
+                    CellState::Unsolved(_hint) => CellState::Unsolved(hint),
+                    CellState::Shaded(_hint) => CellState::Shaded(hint),
+                    CellState::Unshaded(_hint) => CellState::Unshaded(hint),
+                };
             }
         }
         self
@@ -174,11 +405,8 @@ impl PictureGrid {
         let mut num = 0;
         for a in (x - 1)..(x + 2) {
             for b in (y - 1)..(y + 2) {
-                if let Some(cell) = self.get(a, b) {
-                    match cell {
-                        CellState::Shaded(_hint) => num += 1,
-                        _ => {}
-                    }
+                if let Some(CellState::Shaded(_hint)) = self.get(a, b) {
+                    num += 1;
                 }
             }
         }
@@ -190,11 +418,8 @@ impl PictureGrid {
         let mut num = 0;
         for a in (x - 1)..(x + 2) {
             for b in (y - 1)..(y + 2) {
-                if let Some(cell) = self.get(a, b) {
-                    match cell {
-                        CellState::Unshaded(_hint) => num += 1,
-                        _ => {}
-                    }
+                if let Some(CellState::Unshaded(_hint)) = self.get(a, b) {
+                    num += 1;
                 }
             }
         }
@@ -206,17 +431,55 @@ impl PictureGrid {
         let mut num = 0;
         for a in (x - 1)..(x + 2) {
             for b in (y - 1)..(y + 2) {
-                if let Some(cell) = self.get(a, b) {
-                    match cell {
-                        CellState::Unsolved(_hint) => num += 1,
-                        _ => {}
-                    }
+                if let Some(CellState::Unsolved(_hint)) = self.get(a, b) {
+                    num += 1;
                 }
             }
         }
         num
     }
 
+    /// Finds if the clue at (x,y) can no longer be satisfied
+    pub fn clue_violation(&self, x: isize, y: isize) -> Option<ClueViolation> {
+        if x < 0 || x >= self.width as isize || y < 0 || y >= self.height as isize {
+            return None;
+        }
+        let hint = self.get(x, y)?.hint();
+        if hint == PictureGrid::EMPTY {
+            return None;
+        }
+        let shaded = self.num_shaded(x, y);
+        let unsolved = self.num_unsolved(x, y);
+        let kind = if shaded > hint {
+            ViolationKind::TooManyShaded
+        } else if shaded + unsolved < hint {
+            ViolationKind::TooFewShaded
+        } else {
+            return None;
+        };
+        Some(ClueViolation {
+            x,
+            y,
+            hint,
+            shaded,
+            unsolved,
+            kind,
+        })
+    }
+
+    /// Finds every clue that can no longer be satisfied
+    pub fn validate(&self) -> Vec<ClueViolation> {
+        let mut ret_val = Vec::new();
+        for y in 0..self.height as isize {
+            for x in 0..self.width as isize {
+                if let Some(violation) = self.clue_violation(x, y) {
+                    ret_val.push(violation);
+                }
+            }
+        }
+        ret_val
+    }
+
     /// Finds if surrounding grid is complete (no unsolved)
     pub fn is_complete(&self, x: isize, y: isize) -> bool {
         let mut num_complete = 0;
@@ -234,3 +497,162 @@ impl PictureGrid {
         num_complete == 9
     }
 }
+
+impl fmt::Display for PictureGrid {
+    /// Renders the grid as ASCII ('#' shaded, '.' unshaded, '?' unsolved)
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for y in 0..self.height as isize {
+            for x in 0..self.width as isize {
+                let symbol = match self.get(x, y) {
+                    Some(CellState::Shaded(_hint)) => '#',
+                    Some(CellState::Unshaded(_hint)) => '.',
+                    _ => '?',
+                };
+                write!(f, "{}", symbol)?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use text_format::parse_text;
+
+    /// Deserializes the JSON, returning the error message without its position
+    fn deserialize_err(json: &str) -> String {
+        let message = serde_json::from_str::<PictureGrid>(json)
+            .err()
+            .unwrap()
+            .to_string();
+        match message.rfind(" at line ") {
+            Some(pos) => message[..pos].to_string(),
+            None => message,
+        }
+    }
+
+    #[test]
+    fn deserializes_saved_grid() {
+        let mut picgrid = PictureGrid::new(2, 1);
+        picgrid.set(1, 0, CellState::Shaded(3));
+        picgrid.solution = Some(vec![false, true]);
+        let json = serde_json::to_string(&picgrid).unwrap();
+        let loaded: PictureGrid = serde_json::from_str(&json).unwrap();
+        assert_eq!(loaded.cells, picgrid.cells);
+        assert_eq!(loaded.solution, picgrid.solution);
+    }
+
+    #[test]
+    fn rejects_cells_not_matching_size() {
+        let json = r#"{"width": 2, "height": 1, "cells": {"0": {"Unsolved": 1}}}"#;
+        assert_eq!(
+            deserialize_err(json),
+            "2 problems: 2x1 grid needs 2 cells, found 1; cell 1 (1,0) is missing"
+        );
+
+        let json =
+            r#"{"width": 1, "height": 1, "cells": {"0": {"Unsolved": 1}, "1": {"Unsolved": 1}}}"#;
+        assert_eq!(
+            deserialize_err(json),
+            "2 problems: 1x1 grid needs 1 cells, found 2; cell 1 is outside of the grid"
+        );
+
+        let json = r##"{"width": 1, "height": 1, "cells": {"0": {"Unsolved": 1}},
+                        "solution": "#."}"##;
+        assert_eq!(
+            deserialize_err(json),
+            "solution of a 1x1 grid needs 1 cells, found 2"
+        );
+    }
+
+    #[test]
+    fn rejects_duplicate_cells_and_invalid_hints() {
+        let json =
+            r#"{"width": 2, "height": 1, "cells": {"0": {"Unsolved": 1}, "0": {"Shaded": 2}}}"#;
+        assert_eq!(
+            deserialize_err(json),
+            "2 problems: cell 0 (0,0) appears more than once; cell 1 (1,0) is missing"
+        );
+
+        let json = r#"{"width": 1, "height": 1, "cells": {"0": {"Shaded": 11}}}"#;
+        assert_eq!(
+            deserialize_err(json),
+            "cell 0 (0,0) has hint 11 (must be 0-9 or 10)"
+        );
+    }
+
+    #[test]
+    fn wrong_cells_differ_from_solution() {
+        let mut picgrid = PictureGrid::new(2, 2);
+        let solution = [true, false, true, false];
+        assert!(picgrid.wrong_cells(&solution).is_empty());
+
+        picgrid.set_state(0, 0, CellState::Shaded(0));
+        picgrid.set_state(1, 0, CellState::Shaded(0));
+        picgrid.set_state(0, 1, CellState::Unshaded(0));
+        picgrid.set_state(1, 1, CellState::Unshaded(0));
+        assert_eq!(picgrid.wrong_cells(&solution), vec![[1, 0], [0, 1]]);
+
+        // unsolved cells are never wrong
+        picgrid.set_state(1, 0, CellState::Unsolved(0));
+        assert_eq!(picgrid.wrong_cells(&solution), vec![[0, 1]]);
+    }
+
+    #[test]
+    fn finds_too_many_shaded() {
+        let picgrid = parse_text("1.\n..\n\n##\n..\n").unwrap();
+        let violation = picgrid.clue_violation(0, 0).unwrap();
+        assert_eq!(violation.kind, ViolationKind::TooManyShaded);
+        assert_eq!(
+            (violation.hint, violation.shaded, violation.unsolved),
+            (1, 2, 2)
+        );
+        assert_eq!(violation.to_string(), "(0,0) hint 1: 2 shaded");
+    }
+
+    #[test]
+    fn finds_too_few_shaded() {
+        let picgrid = parse_text("4.\n..\n\nx.\n..\n").unwrap();
+        let violation = picgrid.clue_violation(0, 0).unwrap();
+        assert_eq!(violation.kind, ViolationKind::TooFewShaded);
+        assert_eq!(
+            violation.to_string(),
+            "(0,0) hint 4: only 0 shaded and 3 unsolved"
+        );
+
+        // a corner clue with more neighbors than the grid has
+        let picgrid = parse_text("9\n").unwrap();
+        let violation = picgrid.clue_violation(0, 0).unwrap();
+        assert_eq!(violation.kind, ViolationKind::TooFewShaded);
+        assert_eq!(
+            violation.to_string(),
+            "(0,0) hint 9: only 0 shaded and 1 unsolved"
+        );
+    }
+
+    #[test]
+    fn ignores_cells_without_clue_or_off_grid() {
+        let picgrid = parse_text("9.\n..\n").unwrap();
+        assert!(picgrid.clue_violation(1, 0).is_none());
+        assert!(picgrid.clue_violation(-1, 0).is_none());
+        assert!(picgrid.clue_violation(2, 0).is_none());
+        assert!(picgrid.clue_violation(0, -1).is_none());
+        assert!(picgrid.clue_violation(0, 2).is_none());
+
+        let violations = picgrid.validate();
+        assert_eq!(violations.len(), 1);
+        assert_eq!((violations[0].x, violations[0].y), (0, 0));
+        assert!(parse_text("1.\n..\n").unwrap().validate().is_empty());
+    }
+
+    #[test]
+    fn solution_text_round_trip() {
+        let solution = vec![true, false, false, true];
+        assert_eq!(solution_to_text(&solution), "#..#");
+        assert_eq!(solution_from_text("#.\n .#\n"), Ok(solution));
+        // the index counts cells, not whitespace
+        assert_eq!(solution_from_text("#.\n x#"), Err((2, 'x')));
+    }
+}
@@ -1,12 +1,115 @@
 //! PictureGrid controller.
 
+use std::collections::HashSet;
+use std::io;
+use std::time::{SystemTime, UNIX_EPOCH};
+
 use piston_window::generic_event::GenericEvent;
 use piston_window::{Button, Key, MouseButton};
 
-use picgrid::CellState;
+use export::PictureExport;
+use history::{CellChange, EditHistory};
+use picgrid::{CellState, SaveMetadata};
+use solver::{count_solutions, find_deduction, Deduction, Sweep, Technique};
+use theme::Theme;
+use view_settings::PictureGridViewSettings;
 
 use PictureGrid;
 
+/// Factor the grid is zoomed by per step of the mouse wheel
+const ZOOM_STEP: f64 = 1.25;
+/// Largest cell size (in pixels) the grid can be zoomed to
+const MAX_ZOOMED_CELL_SIZE: f64 = 200.0;
+/// Distance (in pixels) the grid is panned by per step of the mouse wheel
+const PAN_STEP: f64 = 40.0;
+
+/// A click-and-drag painting of cells, undone as one action
+struct PaintStroke {
+    /// State given to every cell swept over (the first cell's resulting state)
+    state: CellState,
+    /// Last cell painted (None while the cursor is outside of the grid)
+    last_pos: Option<[isize; 2]>,
+    /// Changes made so far
+    changes: Vec<CellChange>,
+}
+
+/// Finds the cells on a line from one cell to another (excluding the first)
+fn line_cells(from: [isize; 2], to: [isize; 2]) -> Vec<[isize; 2]> {
+    let (dx, dy) = (to[0] - from[0], to[1] - from[1]);
+    let steps = dx.abs().max(dy.abs());
+    (1..=steps)
+        .map(|step| {
+            let fraction = step as f64 / steps as f64;
+            [
+                from[0] + (dx as f64 * fraction).round() as isize,
+                from[1] + (dy as f64 * fraction).round() as isize,
+            ]
+        })
+        .collect()
+}
+
+/// Describes a number of cells (e.g. "1 unsolved cell", "3 unsolved cells")
+fn count_cells(count: usize, kind: &str) -> String {
+    if count == 1 {
+        format!("1 {} cell", kind)
+    } else {
+        format!("{} {} cells", count, kind)
+    }
+}
+
+/// Explains why a deduction holds (before it is applied), as lines of text
+fn explain_deduction(picgrid: &PictureGrid, deduction: &Deduction) -> Vec<String> {
+    let clue = |pos: [isize; 2]| {
+        let hint = picgrid
+            .get(pos[0], pos[1])
+            .map_or(PictureGrid::EMPTY, |cell| cell.hint());
+        format!("clue {} at ({},{})", hint, pos[0], pos[1])
+    };
+    let shaded = deduction
+        .cells
+        .iter()
+        .filter(|(_pos, state)| matches!(state, CellState::Shaded(_)))
+        .count();
+    let unshaded = deduction.cells.len() - shaded;
+
+    match deduction.technique {
+        Technique::SingleClue => {
+            let pos = deduction.clues[0];
+            let (hint, num_shaded) = (
+                picgrid.get(pos[0], pos[1]).map_or(0, |cell| cell.hint()),
+                picgrid.num_shaded(pos[0], pos[1]),
+            );
+            if shaded > 0 {
+                vec![
+                    format!("Hint: {}", clue(pos)),
+                    format!("needs {} more shaded,", hint.saturating_sub(num_shaded)),
+                    format!("so shade its {}", count_cells(shaded, "unsolved")),
+                ]
+            } else {
+                vec![
+                    format!("Hint: {}", clue(pos)),
+                    format!("already has {} shaded,", num_shaded),
+                    format!("so unshade its {}", count_cells(unshaded, "unsolved")),
+                ]
+            }
+        }
+        Technique::ClueOverlap => {
+            let mut forced = Vec::new();
+            if shaded > 0 {
+                forced.push(format!("shade {}", count_cells(shaded, "highlighted")));
+            }
+            if unshaded > 0 {
+                forced.push(format!("unshade {}", count_cells(unshaded, "highlighted")));
+            }
+            vec![
+                format!("Hint: {} and", clue(deduction.clues[0])),
+                format!("{} overlap,", clue(deduction.clues[1])),
+                format!("so {}", forced.join(" and ")),
+            ]
+        }
+    }
+}
+
 /// Handles events for Fill-a-Pix grid.
 pub struct PictureGridController {
     /// Stores the picture grid state.
@@ -15,25 +118,100 @@ pub struct PictureGridController {
     pub cell_pos: Option<[isize; 2]>,
     /// Stores the last cursor position
     pub cursor_pos: [f64; 2],
+    /// Stores the keyboard cursor cell (shown once a movement key is pressed)
+    pub key_cursor: Option<[isize; 2]>,
     /// Stores if the solving algorithm is active
     pub is_solving: bool,
-    /// Stores if solving algorithm needs another pass
-    algorithm_needs_pass: bool,
+    /// Stores the zoom of the grid (1 fits the grid to the window)
+    pub zoom: f64,
+    /// Stores the (x,y) offset of the grid from its fitted position in pixels
+    pub pan: [f64; 2],
+    /// Stores the seconds spent playing (including earlier sessions of a saved game)
+    pub elapsed_secs: f64,
+    /// Stores the path the game is saved to (saving is disabled without one)
+    pub save_path: Option<String>,
+    /// Stores the path of the original puzzle file (if known)
+    pub original: Option<String>,
+    /// Stores the path (without extension) the picture is exported to (disabled without one)
+    pub export_path: Option<String>,
+    /// Stores the deduction shown as a hint until it is applied (or the grid changes)
+    pub hint: Option<Deduction>,
+    /// Stores the themes to switch between (the view keeps its own colors without any)
+    pub themes: Vec<Theme>,
+    /// Stores the position of the current theme in the themes
+    pub theme_index: usize,
+    /// Stores the state of the solving algorithm
+    sweep: Sweep,
     /// Determines how many steps to perform per update event
     steps_per_update: u16,
+    /// Stores the undo/redo history of cell edits
+    history: EditHistory,
+    /// Stores the painting in progress while a mouse button is held down
+    stroke: Option<PaintStroke>,
+    /// Stores if a control key is held down
+    ctrl_down: bool,
+    /// Stores if a shift key is held down
+    shift_down: bool,
+    /// Stores if the grid is being dragged with the middle mouse button
+    is_panning: bool,
+    /// Stores the outcome of the last save (if any)
+    save_status: Option<String>,
+    /// Stores a requested export (with or without clues) until it is performed
+    export_request: Option<bool>,
+    /// Stores the outcome of the last export (if any)
+    export_status: Option<String>,
+    /// Stores why no hint is shown after one was asked for (if any)
+    hint_status: Option<String>,
+    /// Stores if every wrong cell is marked as soon as it is placed (strict mode)
+    strict: bool,
+    /// Stores the solution to check against: the puzzle's own, or one found by the solver (kept
+    /// out of the grid so that saved games do not give it away)
+    solution: Option<Vec<bool>>,
+    /// Stores the wrong cells found by the last check (until they change)
+    checked_cells: HashSet<[isize; 2]>,
+    /// Stores the outcome of the last check (if any)
+    check_status: Option<String>,
 }
 
 impl PictureGridController {
     /// Creates a new picgrid controller.
     pub fn new(picgrid: PictureGrid) -> PictureGridController {
-        let initial_steps = picgrid.width * 2;
+        let initial_steps = picgrid.width.saturating_mul(2);
+        let (elapsed_secs, original) = match picgrid.metadata {
+            Some(ref metadata) => (metadata.elapsed_secs, metadata.original.clone()),
+            None => (0.0, None),
+        };
+        let solution = picgrid.solution.clone();
         PictureGridController {
-            picgrid: picgrid,
+            picgrid,
             cell_pos: None,
             cursor_pos: [0.0; 2],
+            key_cursor: None,
             is_solving: false,
-            algorithm_needs_pass: false,
+            zoom: 1.0,
+            pan: [0.0; 2],
+            elapsed_secs,
+            save_path: None,
+            original,
+            export_path: None,
+            hint: None,
+            themes: Vec::new(),
+            theme_index: 0,
+            sweep: Sweep::new(),
             steps_per_update: initial_steps,
+            history: EditHistory::new(),
+            stroke: None,
+            ctrl_down: false,
+            shift_down: false,
+            is_panning: false,
+            save_status: None,
+            export_request: None,
+            export_status: None,
+            hint_status: None,
+            strict: false,
+            solution,
+            checked_cells: HashSet::new(),
+            check_status: None,
         }
     }
 
@@ -41,63 +219,556 @@ impl PictureGridController {
     pub fn get_messages(&self) -> Vec<String> {
         let mut ret_val = vec![
             "Press 'x' to toggle algorithm".to_string(),
+            "Arrows/HJKL move, Space shade, '.' unshade, Backspace clear".to_string(),
+            "Press '?' or F1 for a hint".to_string(),
+            format!(
+                "Press 'c' to check, Shift+C for strict mode ({})",
+                if self.strict { "on" } else { "off" }
+            ),
             format!("Steps per Update: {} ('+'/'-')", self.steps_per_update),
+            format!(
+                "Zoom: {:.0}% (wheel, Shift+wheel/middle drag pans, '0' fits)",
+                self.zoom * 100.0
+            ),
+            format!(
+                "History: {} undo / {} redo (Ctrl+Z/Ctrl+Y)",
+                self.history.undo_len(),
+                self.history.redo_len()
+            ),
         ];
+        if self.themes.len() > 1 {
+            if let Some(theme) = self.theme() {
+                ret_val.push(format!("Theme: {} ('t' to switch)", theme.name));
+            }
+        }
+        let elapsed = self.elapsed_secs as u64;
+        ret_val.push(format!("Time: {}:{:02}", elapsed / 60, elapsed % 60));
+        if self.save_path.is_some() {
+            ret_val.push(match self.save_status {
+                Some(ref status) => format!("Save (Ctrl+S): {}", status),
+                None => "Save (Ctrl+S)".to_string(),
+            });
+        }
+        if self.export_path.is_some() {
+            ret_val.push(match self.export_status {
+                Some(ref status) => format!("Export (Ctrl+E, +Shift for clues): {}", status),
+                None => "Export (Ctrl+E, +Shift for clues)".to_string(),
+            });
+        }
         if let Some(pos) = self.cell_pos {
             if self.is_solving {
                 ret_val.push(format!("Processing ({},{})", pos[0], pos[1]));
+            } else if let Some(cell) = self.picgrid.get(pos[0], pos[1]) {
+                if cell.hint() < PictureGrid::EMPTY {
+                    // cells outside of the grid count as unshaded, but are not shown
+                    let span = |first: isize, len: u16| {
+                        ((first - 1).max(0)..(first + 2).min(len as isize)).count() as u8
+                    };
+                    let outside =
+                        9 - (span(pos[0], self.picgrid.width) * span(pos[1], self.picgrid.height));
+                    ret_val.push(format!(
+                        "hint {}: {} shaded, {} unshaded, {} unsolved",
+                        cell.hint(),
+                        self.picgrid.num_shaded(pos[0], pos[1]),
+                        self.picgrid.num_unshaded(pos[0], pos[1]) - outside,
+                        self.picgrid.num_unsolved(pos[0], pos[1])
+                    ));
+                }
             }
         }
+        let violations = self.picgrid.validate();
+        if !violations.is_empty() {
+            ret_val.push(format!("Mistakes: {}", violations.len()));
+        }
+        if let Some(technique) = self.sweep.last_technique() {
+            ret_val.push(format!("Last technique: {}", technique));
+        }
+        if let Some(ref status) = self.check_status {
+            ret_val.push(format!("Check: {}", status));
+        }
+        if let Some(ref deduction) = self.hint {
+            ret_val.extend(explain_deduction(&self.picgrid, deduction));
+            ret_val.push("Press Enter to apply it".to_string());
+        } else if let Some(ref status) = self.hint_status {
+            ret_val.push(format!("Hint: {}", status));
+        }
         ret_val
     }
 
+    /// Returns the current theme (if any)
+    pub fn theme(&self) -> Option<&Theme> {
+        self.themes.get(self.theme_index)
+    }
+
+    /// Saves the game (grid, elapsed time and original puzzle) to the save path
+    pub fn save(&mut self) -> io::Result<()> {
+        let path = match self.save_path {
+            Some(ref path) => path.clone(),
+            None => return Err(io::Error::other("no save path")),
+        };
+        let saved_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_secs())
+            .unwrap_or(0);
+        self.picgrid.metadata = Some(SaveMetadata {
+            elapsed_secs: self.elapsed_secs,
+            original: self.original.clone(),
+            saved_at,
+        });
+
+        let result = self.picgrid.save(&path);
+        self.save_status = Some(match result {
+            Ok(()) => format!("saved to {}", path),
+            Err(ref err) => format!("failed ({})", err),
+        });
+        result
+    }
+
+    /// Exports the picture (optionally with the clues) as PNG and SVG next to the export path
+    pub fn export(&mut self, settings: &PictureGridViewSettings, clues: bool) -> io::Result<()> {
+        let path = match self.export_path {
+            Some(ref path) if clues => format!("{}.clues", path),
+            Some(ref path) => path.clone(),
+            None => return Err(io::Error::other("no export path")),
+        };
+        let (png_path, svg_path) = (format!("{}.png", path), format!("{}.svg", path));
+        let picture = PictureExport::new(&self.picgrid, settings).with_clues(clues);
+
+        let result = picture
+            .save(&png_path)
+            .and_then(|()| picture.save(&svg_path));
+        self.export_status = Some(match result {
+            Ok(()) => format!("exported {} and {}", png_path, svg_path),
+            Err(ref err) => format!("failed ({})", err),
+        });
+        result
+    }
+
+    /// Performs an export requested with Ctrl+E (if any)
+    pub fn export_if_requested(&mut self, settings: &PictureGridViewSettings) {
+        if let Some(clues) = self.export_request.take() {
+            // the outcome is shown in the messages
+            self.export(settings, clues).ok();
+        }
+    }
+
+    /// Shows a deduction as a hint, or hides the hint if one is shown
+    fn toggle_hint(&mut self) {
+        if self.hint.take().is_some() {
+            return;
+        }
+        self.hint_status = None;
+        if !self.picgrid.validate().is_empty() {
+            self.hint_status = Some("undo the mistakes first".to_string());
+            return;
+        }
+        self.hint = find_deduction(&self.picgrid);
+        if self.hint.is_none() {
+            self.hint_status = Some(if self.picgrid.is_solved() {
+                "the puzzle is solved".to_string()
+            } else {
+                "no clue rule applies (try a guess)".to_string()
+            });
+        }
+    }
+
+    /// Applies the hint shown (if any), recording it in the history
+    fn apply_hint(&mut self) {
+        if let Some(deduction) = self.hint.take() {
+            self.check_status = None;
+            deduction.apply(&mut self.picgrid);
+            self.history.record_deduction(&self.picgrid, &deduction);
+        }
+    }
+
+    /// Makes sure the grid has a solution to check against, solving the clues if the puzzle
+    /// came without one. Returns false if the clues have no solution or more than one.
+    fn ensure_solution(&mut self) -> bool {
+        if self.solution.is_none() {
+            let mut clues = self.picgrid.clone();
+            for cell in clues.cells.iter_mut() {
+                *cell = CellState::Unsolved(cell.hint());
+            }
+            let count = count_solutions(&clues, 2);
+            if count.is_unique() {
+                self.solution = Some(
+                    count.solutions[0]
+                        .cells
+                        .iter()
+                        .map(|cell| matches!(cell, CellState::Shaded(_)))
+                        .collect(),
+                );
+            } else if count.count() == 0 {
+                self.check_status = Some("no solution found".to_string());
+            } else {
+                self.check_status = Some("puzzle has more than one solution".to_string());
+            }
+        }
+        self.solution.is_some()
+    }
+
+    /// Returns the cells that differ from the solution (none without a solution)
+    fn wrong_cells(&self) -> Vec<[isize; 2]> {
+        match self.solution {
+            Some(ref solution) => self.picgrid.wrong_cells(solution),
+            None => Vec::new(),
+        }
+    }
+
+    /// Compares the grid to the solution, marking every wrong cell
+    pub fn check(&mut self) {
+        if !self.ensure_solution() {
+            return;
+        }
+        let wrong = self.wrong_cells();
+        self.check_status = Some(match wrong.len() {
+            0 if self.picgrid.is_solved() => "solved, no mistakes".to_string(),
+            0 => "no mistakes so far".to_string(),
+            1 => "1 wrong cell".to_string(),
+            count => format!("{} wrong cells", count),
+        });
+        self.checked_cells = wrong.into_iter().collect();
+    }
+
+    /// Turns strict mode (marking every wrong cell as soon as it is placed) on or off
+    pub fn set_strict(&mut self, strict: bool) {
+        self.strict = strict && self.ensure_solution();
+    }
+
+    /// Returns the cells marked as wrong: every wrong cell in strict mode, otherwise the cells
+    /// found by the last check that are still wrong
+    pub fn marked_wrong_cells(&self) -> Vec<[isize; 2]> {
+        let wrong = self.wrong_cells();
+        if self.strict {
+            wrong
+        } else {
+            wrong
+                .into_iter()
+                .filter(|pos| self.checked_cells.contains(pos))
+                .collect()
+        }
+    }
+
+    /// Hides the hint (the grid changed, which may make it wrong)
+    fn clear_hint(&mut self) {
+        self.hint = None;
+        self.hint_status = None;
+    }
+
+    /// Sets the state of a cell (keeping its hint). Returns the change (None if unchanged).
+    fn apply_state(&mut self, pos: [isize; 2], state: CellState) -> Option<CellChange> {
+        let cell = self.picgrid.get(pos[0], pos[1])?;
+        self.picgrid.set_state(pos[0], pos[1], state);
+        let new_state = self.picgrid.get(pos[0], pos[1]).unwrap_or(cell);
+        if new_state != cell {
+            self.clear_hint();
+            self.check_status = None;
+            self.checked_cells.remove(&pos);
+            Some(CellChange {
+                x: pos[0],
+                y: pos[1],
+                before: cell,
+                after: new_state,
+            })
+        } else {
+            None
+        }
+    }
+
+    /// Sets the state of a cell (keeping its hint), recording the change in the history
+    fn mark_cell(&mut self, pos: [isize; 2], state: CellState) {
+        if let Some(change) = self.apply_state(pos, state) {
+            self.history.record(vec![change]);
+        }
+    }
+
+    /// Starts painting with the state at the cell
+    fn begin_stroke(&mut self, pos: [isize; 2], state: CellState) {
+        self.end_stroke();
+        let changes = self.apply_state(pos, state).into_iter().collect();
+        self.stroke = Some(PaintStroke {
+            state,
+            last_pos: Some(pos),
+            changes,
+        });
+        if self.key_cursor.is_some() {
+            self.key_cursor = Some(pos);
+        }
+    }
+
+    /// Paints every cell between the last painted cell and the hovered cell
+    fn continue_stroke(&mut self) {
+        if let Some(mut stroke) = self.stroke.take() {
+            if let Some(pos) = self.cell_pos {
+                let cells = match stroke.last_pos {
+                    Some(last_pos) => line_cells(last_pos, pos),
+                    None => vec![pos],
+                };
+                for cell_pos in cells {
+                    if let Some(change) = self.apply_state(cell_pos, stroke.state) {
+                        stroke.changes.push(change);
+                    }
+                }
+            }
+            stroke.last_pos = self.cell_pos;
+            self.stroke = Some(stroke);
+        }
+    }
+
+    /// Finishes painting, recording every painted cell as one action
+    fn end_stroke(&mut self) {
+        if let Some(stroke) = self.stroke.take() {
+            self.history.record(stroke.changes);
+        }
+    }
+
+    /// Moves the keyboard cursor (showing it at the hovered or first cell if hidden)
+    fn move_key_cursor(&mut self, dx: isize, dy: isize) {
+        let [x, y] = match self.key_cursor {
+            Some(pos) => [pos[0] + dx, pos[1] + dy],
+            None => self.cell_pos.unwrap_or([0, 0]),
+        };
+        self.key_cursor = Some([
+            x.max(0).min(self.picgrid.width as isize - 1),
+            y.max(0).min(self.picgrid.height as isize - 1),
+        ]);
+    }
+
+    /// Finds the upper left corner of the first cell (the grid rect includes the outer border)
+    fn grid_origin(&self, grid_rect: [f64; 4], cell_size: f64) -> [f64; 2] {
+        let border = grid_rect[2] - (cell_size * f64::from(self.picgrid.width));
+        [grid_rect[0] + (border / 2.0), grid_rect[1] + (border / 2.0)]
+    }
+
+    /// Finds the cell under the cursor, as drawn within the grid area (if any)
+    fn cell_at_cursor(
+        &self,
+        grid_area: [f64; 4],
+        grid_rect: [f64; 4],
+        cell_size: f64,
+    ) -> Option<[isize; 2]> {
+        let [x, y] = self.cursor_pos;
+        if cell_size <= 0.0
+            || x < grid_area[0]
+            || y < grid_area[1]
+            || x >= (grid_area[0] + grid_area[2])
+            || y >= (grid_area[1] + grid_area[3])
+        {
+            return None;
+        }
+        let origin = self.grid_origin(grid_rect, cell_size);
+        let pos = [
+            ((x - origin[0]) / cell_size).floor() as isize,
+            ((y - origin[1]) / cell_size).floor() as isize,
+        ];
+        // (get treats cells outside of the grid as unshaded, so check the bounds instead)
+        if (0..self.picgrid.width as isize).contains(&pos[0])
+            && (0..self.picgrid.height as isize).contains(&pos[1])
+        {
+            Some(pos)
+        } else {
+            None
+        }
+    }
+
+    /// Zooms the grid by the factor, keeping the point under the cursor in place
+    fn zoom_at_cursor(&mut self, grid_rect: [f64; 4], cell_size: f64, factor: f64) {
+        if cell_size <= 0.0 {
+            return;
+        }
+        let fitted_cell_size = cell_size / self.zoom;
+        let max_zoom = (MAX_ZOOMED_CELL_SIZE / fitted_cell_size).max(1.0);
+        let zoom = (self.zoom * factor).max(1.0).min(max_zoom);
+
+        // the distance from the grid origin to the cursor scales with the cells
+        let origin = self.grid_origin(grid_rect, cell_size);
+        let scale = zoom / self.zoom;
+        self.pan[0] -= (self.cursor_pos[0] - origin[0]) * (scale - 1.0);
+        self.pan[1] -= (self.cursor_pos[1] - origin[1]) * (scale - 1.0);
+        self.zoom = zoom;
+    }
+
+    /// Zooms and pans the grid back to fit the window
+    fn fit_to_window(&mut self) {
+        self.zoom = 1.0;
+        self.pan = [0.0; 2];
+    }
+
+    /// Stops the solving algorithm (if active)
+    fn stop_solving(&mut self) {
+        if self.is_solving {
+            self.is_solving = false;
+            self.cell_pos = None;
+        }
+    }
+
     /// Handles events.
-    pub fn event<E: GenericEvent>(&mut self, grid_rect: [f64; 4], cell_size: f64, e: &E) {
+    ///
+    /// The grid area, grid rect and cell size are where the view last drew the grid, so that
+    /// the cursor is hit-tested with the same zoom and pan.
+    pub fn event<E: GenericEvent>(
+        &mut self,
+        grid_area: [f64; 4],
+        grid_rect: [f64; 4],
+        cell_size: f64,
+        e: &E,
+    ) {
+        if let Some(cp) = e.mouse_cursor_args() {
+            if self.is_panning {
+                self.pan[0] += cp[0] - self.cursor_pos[0];
+                self.pan[1] += cp[1] - self.cursor_pos[1];
+            }
+            self.cursor_pos = cp;
+        }
+
+        if let Some(scroll) = e.mouse_scroll_args() {
+            if self.shift_down {
+                self.pan[0] -= scroll[0] * PAN_STEP;
+                self.pan[1] += scroll[1] * PAN_STEP;
+            } else if scroll[0] != 0.0 {
+                // sideways scrolling (e.g. on a touchpad) pans instead of zooming
+                self.pan[0] -= scroll[0] * PAN_STEP;
+            } else {
+                self.zoom_at_cursor(grid_rect, cell_size, ZOOM_STEP.powf(scroll[1]));
+            }
+        }
+
         if !self.is_solving {
-            if let Some(cp) = e.mouse_cursor_args() {
-                self.cursor_pos = cp;
-                if self.cursor_pos[0] > grid_rect[0]
-                    && self.cursor_pos[1] > grid_rect[1]
-                    && self.cursor_pos[0] < (grid_rect[0] + grid_rect[2])
-                    && self.cursor_pos[1] < (grid_rect[1] + grid_rect[3])
-                {
-                    // we're within the grid, so see what cell we're in (size + border width)
-                    self.cell_pos = Some([
-                        ((self.cursor_pos[0] - grid_rect[0]) / (cell_size + 1.0)).trunc() as isize,
-                        ((self.cursor_pos[1] - grid_rect[1]) / (cell_size + 1.0)).trunc() as isize,
-                    ]);
-                } else {
-                    self.cell_pos = None;
-                }
-            }
-
-            if let Some(Button::Mouse(MouseButton::Left)) = e.press_args() {
+            if e.mouse_cursor_args().is_some() {
+                self.cell_pos = self.cell_at_cursor(grid_area, grid_rect, cell_size);
+                self.continue_stroke();
+            }
+
+            if let Some(Button::Mouse(button)) = e.press_args() {
+                // the grid may have been zoomed or panned since the cursor last moved
+                self.cell_pos = self.cell_at_cursor(grid_area, grid_rect, cell_size);
                 if let Some(pos) = self.cell_pos {
                     if let Some(cell) = self.picgrid.get(pos[0], pos[1]) {
-                        let new_state;
-                        match cell {
-                            CellState::Unsolved(val) => new_state = CellState::Shaded(val),
-                            CellState::Shaded(val) => new_state = CellState::Unshaded(val),
-                            CellState::Unshaded(val) => new_state = CellState::Unsolved(val),
+                        // left click cycles the state, right click marks unshaded
+                        let new_state = match (button, cell) {
+                            (MouseButton::Left, CellState::Unsolved(val)) => {
+                                Some(CellState::Shaded(val))
+                            }
+                            (MouseButton::Left, CellState::Shaded(val)) => {
+                                Some(CellState::Unshaded(val))
+                            }
+                            (MouseButton::Left, CellState::Unshaded(val)) => {
+                                Some(CellState::Unsolved(val))
+                            }
+                            (MouseButton::Right, _) => Some(CellState::Unshaded(cell.hint())),
+                            _ => None,
                         };
-                        self.picgrid.set(pos[0], pos[1], new_state);
+                        if let Some(new_state) = new_state {
+                            self.begin_stroke(pos, new_state);
+                        }
                     }
                 }
             }
+
+            if let Some(Button::Keyboard(key)) = e.press_args() {
+                let marked_state = match key {
+                    Key::Space => Some(CellState::Shaded(0)),
+                    Key::Period => Some(CellState::Unshaded(0)),
+                    Key::Backspace | Key::Delete => Some(CellState::Unsolved(0)),
+                    _ => None,
+                };
+                match key {
+                    Key::Left | Key::H => self.move_key_cursor(-1, 0),
+                    Key::Right | Key::L => self.move_key_cursor(1, 0),
+                    Key::Up | Key::K => self.move_key_cursor(0, -1),
+                    Key::Down | Key::J => self.move_key_cursor(0, 1),
+                    _ => {}
+                }
+                if let (Some(state), Some(pos)) = (marked_state, self.key_cursor) {
+                    self.mark_cell(pos, state);
+                }
+                match key {
+                    Key::Slash | Key::Question | Key::F1 => self.toggle_hint(),
+                    Key::Return | Key::NumPadEnter => self.apply_hint(),
+                    _ => {}
+                }
+            }
+        }
+
+        if let Some(Button::Mouse(MouseButton::Middle)) = e.press_args() {
+            self.is_panning = true;
+        }
+
+        if let Some(false) = e.focus_args() {
+            // key releases are not received while the window is unfocused
+            self.ctrl_down = false;
+            self.shift_down = false;
+        }
+
+        match e.release_args() {
+            Some(Button::Mouse(MouseButton::Middle)) => {
+                self.is_panning = false;
+            }
+            Some(Button::Mouse(MouseButton::Left)) | Some(Button::Mouse(MouseButton::Right)) => {
+                self.end_stroke();
+            }
+            Some(Button::Keyboard(Key::LCtrl)) | Some(Button::Keyboard(Key::RCtrl)) => {
+                self.ctrl_down = false;
+            }
+            Some(Button::Keyboard(Key::LShift)) | Some(Button::Keyboard(Key::RShift)) => {
+                self.shift_down = false;
+            }
+            _ => {}
         }
 
         if let Some(Button::Keyboard(key)) = e.press_args() {
             match key {
+                Key::LCtrl | Key::RCtrl => {
+                    self.ctrl_down = true;
+                }
+                Key::LShift | Key::RShift => {
+                    self.shift_down = true;
+                }
+                Key::Z if self.ctrl_down => {
+                    self.stop_solving();
+                    self.clear_hint();
+                    self.check_status = None;
+                    self.history.undo(&mut self.picgrid);
+                }
+                Key::Y if self.ctrl_down => {
+                    self.stop_solving();
+                    self.clear_hint();
+                    self.check_status = None;
+                    self.history.redo(&mut self.picgrid);
+                }
+                Key::S if self.ctrl_down => {
+                    // the outcome is shown in the messages
+                    self.save().ok();
+                }
+                Key::E if self.ctrl_down => {
+                    // performed by export_if_requested, which has the view settings
+                    self.export_request = Some(self.shift_down);
+                }
                 Key::X => {
                     self.is_solving = !self.is_solving;
                     if self.is_solving {
-                        self.cell_pos = Some([0, 0]);
+                        self.clear_hint();
+                        self.sweep = Sweep::new();
+                        self.cell_pos = self.sweep.cell_pos;
                     } else {
                         self.cell_pos = None;
                     }
                 }
+                Key::C if self.shift_down => {
+                    let strict = !self.strict;
+                    self.set_strict(strict);
+                }
+                Key::C => {
+                    self.check();
+                }
+                Key::T if !self.themes.is_empty() => {
+                    self.theme_index = (self.theme_index + 1) % self.themes.len();
+                }
+                Key::D0 | Key::NumPad0 => {
+                    self.fit_to_window();
+                }
                 Key::NumPadPlus => {
-                    self.steps_per_update += 1;
+                    self.steps_per_update = self.steps_per_update.saturating_add(1);
                 }
                 Key::NumPadMinus => {
                     self.steps_per_update -= 1;
@@ -109,49 +780,517 @@ impl PictureGridController {
             }
         }
 
-        if let Some(_) = e.update_args() {
+        if let Some(args) = e.update_args() {
+            if !self.picgrid.is_solved() {
+                self.elapsed_secs += args.dt;
+            }
             for _loop in 0..self.steps_per_update {
                 if self.is_solving {
-                    if let Some(cell) = self.cell_pos {
-                        let mut x = cell[0];
-                        let mut y = cell[1];
-                        let (nx, ny, ncell) = self.picgrid.next_incomplete(x, y);
-                        if let Some(cell) = ncell {
-                            x = nx;
-                            y = ny;
-                            let cell_hint = cell.hint();
-                            let cell_shaded = self.picgrid.num_shaded(x, y);
-                            let cell_unsolved = self.picgrid.num_unsolved(x, y);
-                            if cell_hint == cell_shaded {
-                                self.picgrid.fill_unshaded(x, y);
-                                self.algorithm_needs_pass = true;
-                            } else if cell_hint == (cell_shaded + cell_unsolved) {
-                                self.picgrid.fill_shaded(x, y);
-                                self.algorithm_needs_pass = true;
-                            }
-                        } else {
-                            x = -1;
-                            y = 0;
-                        }
-
-                        x += 1;
-                        if x >= self.picgrid.width as isize {
-                            x = 0;
-                            y += 1;
-                        }
-                        if y < self.picgrid.height as isize {
-                            self.cell_pos = Some([x, y]);
-                        } else if self.algorithm_needs_pass {
-                            self.cell_pos = Some([0, 0]);
-                            self.algorithm_needs_pass = false;
-                        } else {
-                            self.cell_pos = None;
-                            self.is_solving = false;
-                            self.algorithm_needs_pass = false;
-                        }
+                    if let Some(deduction) = self.sweep.next_deduction(&mut self.picgrid) {
+                        self.history.record_deduction(&self.picgrid, &deduction);
                     }
+                    self.is_solving = self.sweep.is_active();
+                    self.cell_pos = self.sweep.cell_pos;
                 }
             }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use loader::load_file;
+    use piston_window::{ButtonArgs, ButtonState, Event, Input, Motion};
+    use std::env;
+    use std::fs;
+    use std::process;
+    use text_format::parse_text;
+
+    const AREA: [f64; 4] = [0.0, 0.0, 100.0, 100.0];
+
+    fn controller(text: &str) -> PictureGridController {
+        PictureGridController::new(parse_text(text).unwrap())
+    }
+
+    /// Sends the input with the grid where the view draws it (fitted to AREA, zoomed and panned)
+    fn send(controller: &mut PictureGridController, input: Input) {
+        let width = f64::from(controller.picgrid.width);
+        let height = f64::from(controller.picgrid.height);
+        let cell_size = f64::min(AREA[2] / width, AREA[3] / height) * controller.zoom;
+        let grid_rect = [
+            AREA[0] + controller.pan[0],
+            AREA[1] + controller.pan[1],
+            cell_size * width,
+            cell_size * height,
+        ];
+        controller.event(AREA, grid_rect, cell_size, &Event::Input(input));
+    }
+
+    fn move_to(controller: &mut PictureGridController, pos: [f64; 2]) {
+        send(controller, Input::Move(Motion::MouseCursor(pos[0], pos[1])));
+    }
+
+    fn key(controller: &mut PictureGridController, key: Key, state: ButtonState) {
+        let args = ButtonArgs {
+            state,
+            button: Button::Keyboard(key),
+            scancode: None,
+        };
+        send(controller, Input::Button(args));
+    }
+
+    fn mouse(controller: &mut PictureGridController, button: MouseButton, state: ButtonState) {
+        let args = ButtonArgs {
+            state,
+            button: Button::Mouse(button),
+            scancode: None,
+        };
+        send(controller, Input::Button(args));
+    }
+
+    /// Drags with the button from the first cell through the others (4x4 grids of 25pt cells)
+    fn drag(controller: &mut PictureGridController, button: MouseButton, cells: &[[isize; 2]]) {
+        let center = |pos: [isize; 2]| [pos[0] as f64 * 25.0 + 12.5, pos[1] as f64 * 25.0 + 12.5];
+        move_to(controller, center(cells[0]));
+        mouse(controller, button, ButtonState::Press);
+        for &pos in cells[1..].iter() {
+            move_to(controller, center(pos));
+        }
+        mouse(controller, button, ButtonState::Release);
+    }
+
+    #[test]
+    fn modifiers_are_released_when_focus_is_lost() {
+        let mut controller = controller("1.\n");
+        controller.mark_cell([0, 0], CellState::Shaded(0));
+        key(&mut controller, Key::LCtrl, ButtonState::Press);
+        key(&mut controller, Key::LShift, ButtonState::Press);
+        send(&mut controller, Input::Focus(false));
+        assert!(!controller.ctrl_down);
+        assert!(!controller.shift_down);
+
+        // Z without Ctrl does not undo
+        key(&mut controller, Key::Z, ButtonState::Press);
+        assert_eq!(controller.history.undo_len(), 1);
+    }
+
+    /// Explains the first deduction of the grid
+    fn explain(text: &str) -> Vec<String> {
+        let picgrid = parse_text(text).unwrap();
+        explain_deduction(&picgrid, &find_deduction(&picgrid).unwrap())
+    }
+
+    #[test]
+    fn counts_cells_in_words() {
+        assert_eq!(count_cells(0, "unsolved"), "0 unsolved cells");
+        assert_eq!(count_cells(1, "unsolved"), "1 unsolved cell");
+        assert_eq!(count_cells(2, "highlighted"), "2 highlighted cells");
+    }
+
+    #[test]
+    fn explains_single_clue_deductions() {
+        assert_eq!(
+            explain("1\n"),
+            vec![
+                "Hint: clue 1 at (0,0)",
+                "needs 1 more shaded,",
+                "so shade its 1 unsolved cell"
+            ]
+        );
+        assert_eq!(
+            explain("4.\n..\n\n#.\n..\n"),
+            vec![
+                "Hint: clue 4 at (0,0)",
+                "needs 3 more shaded,",
+                "so shade its 3 unsolved cells"
+            ]
+        );
+        assert_eq!(
+            explain("1.\n\n#.\n"),
+            vec![
+                "Hint: clue 1 at (0,0)",
+                "already has 1 shaded,",
+                "so unshade its 1 unsolved cell"
+            ]
+        );
+        assert_eq!(
+            explain("0.\n"),
+            vec![
+                "Hint: clue 0 at (0,0)",
+                "already has 0 shaded,",
+                "so unshade its 2 unsolved cells"
+            ]
+        );
+    }
+
+    #[test]
+    fn explains_overlap_deductions() {
+        assert_eq!(
+            explain(".12.\n"),
+            vec![
+                "Hint: clue 1 at (1,0) and",
+                "clue 2 at (2,0) overlap,",
+                "so shade 1 highlighted cell and unshade 1 highlighted cell"
+            ]
+        );
+    }
+
+    #[test]
+    fn toggles_hint() {
+        let mut controller = controller(".12.0\n");
+        controller.toggle_hint();
+        assert_eq!(controller.hint.as_ref().unwrap().clues, vec![[4, 0]]);
+        assert!(controller
+            .get_messages()
+            .contains(&"Press Enter to apply it".to_string()));
+        controller.toggle_hint();
+        assert!(controller.hint.is_none());
+
+        controller.toggle_hint();
+        controller.apply_hint();
+        assert!(controller.hint.is_none());
+        assert_eq!(controller.history.undo_len(), 1);
+        assert_eq!(controller.picgrid.num_unsolved_cells(), 3);
+    }
+
+    #[test]
+    fn explains_missing_hint() {
+        let status = |text: &str| {
+            let mut controller = controller(text);
+            controller.toggle_hint();
+            assert!(controller.hint.is_none());
+            controller.hint_status.clone().unwrap()
+        };
+        assert_eq!(status("1.\n\n#x\n"), "the puzzle is solved");
+        assert_eq!(status("..\n"), "no clue rule applies (try a guess)");
+        assert_eq!(status("0.\n\n#.\n"), "undo the mistakes first");
+    }
+
+    /// Returns the status line of the last check
+    fn check_message(controller: &PictureGridController) -> Option<String> {
+        controller
+            .get_messages()
+            .into_iter()
+            .find(|message| message.starts_with("Check: "))
+    }
+
+    #[test]
+    fn check_marks_wrong_cells_until_they_change() {
+        // needs the search to be solved, and has no solution of its own
+        let mut controller = controller("...1\n133.\n1..2\n.11.\n");
+        controller.mark_cell([0, 0], CellState::Shaded(0));
+        controller.mark_cell([1, 1], CellState::Shaded(0));
+        assert!(controller.marked_wrong_cells().is_empty());
+
+        controller.check();
+        assert_eq!(controller.marked_wrong_cells(), vec![[0, 0]]);
+        assert_eq!(check_message(&controller).unwrap(), "Check: 1 wrong cell");
+        // the solution found is not saved with the game
+        assert!(controller.picgrid.solution.is_none());
+
+        // a new mistake is not marked (or counted) until the next check
+        controller.mark_cell([3, 3], CellState::Shaded(0));
+        assert_eq!(controller.marked_wrong_cells(), vec![[0, 0]]);
+        assert!(check_message(&controller).is_none());
+
+        controller.mark_cell([0, 0], CellState::Unsolved(0));
+        controller.mark_cell([0, 0], CellState::Shaded(0));
+        assert_eq!(controller.marked_wrong_cells(), Vec::<[isize; 2]>::new());
+
+        controller.check();
+        assert_eq!(check_message(&controller).unwrap(), "Check: 2 wrong cells");
+        key(&mut controller, Key::LCtrl, ButtonState::Press);
+        key(&mut controller, Key::Z, ButtonState::Press);
+        assert!(check_message(&controller).is_none());
+    }
+
+    #[test]
+    fn check_uses_solution_of_puzzle() {
+        let mut picgrid = parse_text("1.\n").unwrap();
+        picgrid.solution = Some(vec![false, true]);
+        let mut controller = PictureGridController::new(picgrid);
+        controller.mark_cell([1, 0], CellState::Shaded(0));
+        controller.check();
+        assert_eq!(
+            check_message(&controller).unwrap(),
+            "Check: no mistakes so far"
+        );
+        controller.mark_cell([0, 0], CellState::Unshaded(0));
+        controller.check();
+        assert_eq!(
+            check_message(&controller).unwrap(),
+            "Check: solved, no mistakes"
+        );
+        controller.mark_cell([1, 0], CellState::Unshaded(0));
+        controller.check();
+        assert_eq!(controller.marked_wrong_cells(), vec![[1, 0]]);
+    }
+
+    #[test]
+    fn strict_mode_marks_every_wrong_cell() {
+        let mut solvable = controller("0.\n");
+        solvable.set_strict(true);
+        solvable.mark_cell([1, 0], CellState::Shaded(0));
+        assert_eq!(solvable.marked_wrong_cells(), vec![[1, 0]]);
+
+        // without a solution strict mode stays off
+        let mut unsolvable = controller("3.\n");
+        unsolvable.set_strict(true);
+        assert!(!unsolvable.strict);
+        assert_eq!(
+            check_message(&unsolvable).unwrap(),
+            "Check: no solution found"
+        );
+
+        // nor with more than one
+        let mut ambiguous = controller("1.\n");
+        ambiguous.set_strict(true);
+        assert!(!ambiguous.strict);
+        assert_eq!(ambiguous.solution, None);
+        assert_eq!(
+            check_message(&ambiguous).unwrap(),
+            "Check: puzzle has more than one solution"
+        );
+    }
+
+    #[test]
+    fn saved_game_resumes_where_it_stopped() {
+        let path = env::temp_dir().join(format!("fill-a-pix-resume-{}.json", process::id()));
+        let mut playing = controller("0.\n.9\n");
+        playing.mark_cell([0, 0], CellState::Unshaded(0));
+        playing.mark_cell([1, 1], CellState::Shaded(0));
+        playing.elapsed_secs = 75.5;
+        playing.original = Some("puzzles/small.json".to_string());
+        playing.save_path = Some(path.to_string_lossy().into_owned());
+        playing.save().unwrap();
+
+        let loaded = load_file(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.cells, playing.picgrid.cells);
+        let metadata = loaded.metadata.clone().unwrap();
+        assert_eq!(metadata.elapsed_secs, 75.5);
+        assert_eq!(metadata.original.as_ref().unwrap(), "puzzles/small.json");
+
+        let resumed = PictureGridController::new(loaded);
+        assert_eq!(resumed.elapsed_secs, 75.5);
+        assert_eq!(resumed.original, metadata.original);
+        assert_eq!(resumed.picgrid.get(0, 0), Some(CellState::Unshaded(0)));
+        assert_eq!(resumed.picgrid.get(1, 1), Some(CellState::Shaded(9)));
+    }
+
+    #[test]
+    fn key_cursor_starts_at_hovered_cell() {
+        // 3x2 grid of 33pt cells
+        let mut controller = controller("...\n...\n");
+        move_to(&mut controller, [50.0, 50.0]);
+        assert_eq!(controller.key_cursor, None);
+        key(&mut controller, Key::Up, ButtonState::Press);
+        assert_eq!(controller.key_cursor, Some([1, 1]));
+        key(&mut controller, Key::Up, ButtonState::Press);
+        assert_eq!(controller.key_cursor, Some([1, 0]));
+    }
+
+    #[test]
+    fn key_cursor_stops_at_grid_edges() {
+        let mut controller = controller("...\n...\n");
+        // without a hovered cell the cursor starts at the first cell
+        key(&mut controller, Key::Left, ButtonState::Press);
+        assert_eq!(controller.key_cursor, Some([0, 0]));
+        key(&mut controller, Key::Left, ButtonState::Press);
+        key(&mut controller, Key::K, ButtonState::Press);
+        assert_eq!(controller.key_cursor, Some([0, 0]));
+
+        for _press in 0..4 {
+            key(&mut controller, Key::Right, ButtonState::Press);
+            key(&mut controller, Key::J, ButtonState::Press);
+        }
+        assert_eq!(controller.key_cursor, Some([2, 1]));
+    }
+
+    #[test]
+    fn marking_keys_set_state_directly() {
+        let mut controller = controller("4.\n..\n");
+        key(&mut controller, Key::Down, ButtonState::Press);
+        let cell = |controller: &PictureGridController| controller.picgrid.get(0, 0).unwrap();
+
+        // pressing a key twice keeps its state rather than cycling
+        for _press in 0..2 {
+            key(&mut controller, Key::Space, ButtonState::Press);
+            assert_eq!(cell(&controller), CellState::Shaded(4));
+        }
+        for _press in 0..2 {
+            key(&mut controller, Key::Period, ButtonState::Press);
+            assert_eq!(cell(&controller), CellState::Unshaded(4));
+        }
+        key(&mut controller, Key::Backspace, ButtonState::Press);
+        assert_eq!(cell(&controller), CellState::Unsolved(4));
+        key(&mut controller, Key::Space, ButtonState::Press);
+        key(&mut controller, Key::Delete, ButtonState::Press);
+        assert_eq!(cell(&controller), CellState::Unsolved(4));
+        assert_eq!(controller.history.undo_len(), 5);
+    }
+
+    /// Finds the cells with the state (ignoring hints), row by row
+    fn cells_in_state(controller: &PictureGridController, state: CellState) -> Vec<[isize; 2]> {
+        let mut cells = Vec::new();
+        for y in 0..controller.picgrid.height as isize {
+            for x in 0..controller.picgrid.width as isize {
+                let cell = controller.picgrid.get(x, y).unwrap();
+                if std::mem::discriminant(&cell) == std::mem::discriminant(&state) {
+                    cells.push([x, y]);
+                }
+            }
+        }
+        cells
+    }
+
+    #[test]
+    fn drag_fills_every_cell_in_between() {
+        let mut controller = controller("....\n....\n....\n....\n");
+        // the cursor jumps from corner to corner in one move
+        drag(&mut controller, MouseButton::Left, &[[0, 0], [3, 3]]);
+        assert_eq!(
+            cells_in_state(&controller, CellState::Shaded(0)),
+            vec![[0, 0], [1, 1], [2, 2], [3, 3]]
+        );
+        drag(
+            &mut controller,
+            MouseButton::Left,
+            &[[0, 3], [3, 3], [3, 0]],
+        );
+        assert_eq!(
+            cells_in_state(&controller, CellState::Unsolved(0)),
+            vec![[1, 0], [2, 0], [0, 1], [2, 1], [0, 2], [1, 2]]
+        );
+    }
+
+    #[test]
+    fn drag_gives_swept_cells_state_of_first_cell() {
+        let mut controller = controller("....\n....\n....\n....\n");
+        controller.picgrid.set_state(0, 0, CellState::Shaded(0));
+        controller.picgrid.set_state(2, 0, CellState::Shaded(0));
+        controller.picgrid.set_state(3, 0, CellState::Unshaded(0));
+
+        // the first cell cycles from shaded to unshaded, the others are not cycled
+        drag(&mut controller, MouseButton::Left, &[[0, 0], [3, 0]]);
+        assert_eq!(
+            cells_in_state(&controller, CellState::Unshaded(0)),
+            vec![[0, 0], [1, 0], [2, 0], [3, 0]]
+        );
+    }
+
+    #[test]
+    fn drag_is_undone_as_one_action() {
+        let mut controller = controller("....\n....\n....\n....\n");
+        drag(
+            &mut controller,
+            MouseButton::Left,
+            &[[0, 1], [1, 1], [3, 1]],
+        );
+        assert_eq!(cells_in_state(&controller, CellState::Shaded(0)).len(), 4);
+        assert_eq!(controller.history.undo_len(), 1);
+
+        key(&mut controller, Key::LCtrl, ButtonState::Press);
+        key(&mut controller, Key::Z, ButtonState::Press);
+        assert_eq!(
+            cells_in_state(&controller, CellState::Unsolved(0)).len(),
+            16
+        );
+    }
+
+    #[test]
+    fn right_drag_marks_cells_unshaded() {
+        let mut controller = controller("....\n....\n....\n....\n");
+        controller.picgrid.set_state(1, 2, CellState::Shaded(0));
+        drag(&mut controller, MouseButton::Right, &[[0, 2], [3, 2]]);
+        assert_eq!(
+            cells_in_state(&controller, CellState::Unshaded(0)),
+            vec![[0, 2], [1, 2], [2, 2], [3, 2]]
+        );
+        assert_eq!(controller.history.undo_len(), 1);
+    }
+
+    #[test]
+    fn zoom_keeps_cell_under_cursor() {
+        let mut controller = controller("....\n....\n....\n....\n");
+        for &pos in [[60.0, 35.0], [5.0, 95.0], [99.0, 1.0]].iter() {
+            move_to(&mut controller, pos);
+            let cell = controller.cell_pos;
+            for &scroll in [1.0, 2.0, -1.0].iter() {
+                send(
+                    &mut controller,
+                    Input::Move(Motion::MouseScroll(0.0, scroll)),
+                );
+                move_to(&mut controller, pos);
+                assert_eq!(controller.cell_pos, cell, "at {:?}", pos);
+            }
+            assert!(controller.zoom > 1.0);
+        }
+    }
+
+    #[test]
+    fn pan_shifts_cell_under_cursor() {
+        let mut controller = controller("....\n....\n....\n....\n");
+        move_to(&mut controller, [60.0, 35.0]);
+        assert_eq!(controller.cell_pos, Some([2, 1]));
+
+        // drag the grid one cell to the left and down with the middle button
+        mouse(&mut controller, MouseButton::Middle, ButtonState::Press);
+        move_to(&mut controller, [35.0, 60.0]);
+        mouse(&mut controller, MouseButton::Middle, ButtonState::Release);
+        assert_eq!(controller.pan, [-25.0, 25.0]);
+        move_to(&mut controller, [60.0, 35.0]);
+        assert_eq!(controller.cell_pos, Some([3, 0]));
+
+        // the area uncovered by the grid has no cells
+        move_to(&mut controller, [90.0, 10.0]);
+        assert_eq!(controller.cell_pos, None);
+    }
+
+    #[test]
+    fn clicks_outside_grid_area_miss() {
+        let mut controller = controller("....\n....\n....\n....\n");
+        move_to(&mut controller, [50.0, 50.0]);
+        for _scroll in 0..4 {
+            send(&mut controller, Input::Move(Motion::MouseScroll(0.0, 1.0)));
+        }
+
+        // the zoomed grid reaches beyond its area, where it is not drawn
+        move_to(&mut controller, [120.0, 50.0]);
+        assert_eq!(controller.cell_pos, None);
+        mouse(&mut controller, MouseButton::Left, ButtonState::Press);
+        mouse(&mut controller, MouseButton::Left, ButtonState::Release);
+        assert_eq!(
+            cells_in_state(&controller, CellState::Unsolved(0)).len(),
+            16
+        );
+        assert_eq!(controller.history.undo_len(), 0);
+    }
+
+    #[test]
+    fn hover_label_counts_neighbors_of_corner_clue() {
+        // 3x3 grid of 33pt cells
+        let mut controller = controller("4..\n...\n..5\n\n.#.\nx..\n...\n");
+        move_to(&mut controller, [10.0, 10.0]);
+        let label = |controller: &PictureGridController| {
+            controller
+                .get_messages()
+                .into_iter()
+                .find(|message| message.starts_with("hint "))
+        };
+        // the cells outside of the grid are not counted as unshaded
+        assert_eq!(
+            label(&controller).unwrap(),
+            "hint 4: 1 shaded, 1 unshaded, 2 unsolved"
+        );
+
+        move_to(&mut controller, [90.0, 90.0]);
+        assert_eq!(
+            label(&controller).unwrap(),
+            "hint 5: 0 shaded, 0 unshaded, 4 unsolved"
+        );
+        move_to(&mut controller, [50.0, 50.0]);
+        assert_eq!(label(&controller), None);
+    }
+}
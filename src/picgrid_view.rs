@@ -1,5 +1,7 @@
 //! PictureGrid view.
 
+use std::collections::HashSet;
+
 use piston_window::character::CharacterCache;
 use piston_window::context::Context;
 use piston_window::line::Line;
@@ -9,68 +11,10 @@ use piston_window::types::Color;
 use piston_window::{Graphics, Transformed};
 
 use picgrid::{CellState, PictureGrid};
+pub use view_settings::PictureGridViewSettings;
+use view_settings::hint_text_layout;
 use PictureGridController;
 
-/// Stores picgrid view settings.
-pub struct PictureGridViewSettings {
-    /// (x,y) position of upper left corner of controls area
-    pub controls_position: [f64; 2],
-    /// (x,y) position of upper left corner of grid
-    pub grid_position: [f64; 2],
-    /// (h,v) side margins within view port
-    pub margin: [f64; 2],
-    /// size of label text
-    pub label_size: u32,
-    /// color of control label text
-    pub label_color: Color,
-    /// width/height of grid cells (only if viewport cannot be determined)
-    pub cell_size: f64,
-    /// color of grid borders
-    pub grid_border_color: Color,
-    /// width of outer grid border
-    pub grid_border_width: f64,
-    /// width of cell grid border
-    pub cell_border_width: f64,
-    /// color of hint text (when cell is unsolved)
-    pub cell_unsolved_hint_text_color: Color,
-    /// background color of cell (when cell is unsolved)
-    pub cell_unsolved_background_color: Color,
-    /// color of hint text (when cell is solved as shaded)
-    pub cell_solved_shaded_hint_text_color: Color,
-    /// background color of cell (when cell is solved as shaded)
-    pub cell_solved_shaded_background_color: Color,
-    /// color of hint text (when cell is solved as unshaded)
-    pub cell_solved_unshaded_hint_text_color: Color,
-    /// background color of cell (when cell is solved as unshaded)
-    pub cell_solved_unshaded_background_color: Color,
-    /// color of current cell being processed by algorithm
-    pub cell_current_color: Color,
-}
-
-impl PictureGridViewSettings {
-    /// Creates new picgrid view settings.
-    pub fn new() -> PictureGridViewSettings {
-        PictureGridViewSettings {
-            controls_position: [15.0; 2],
-            grid_position: [260.0, 15.0],
-            margin: [15.0; 2],
-            label_size: 15,
-            label_color: [0.0, 0.0, 0.0, 1.0],
-            cell_size: 50.0,
-            grid_border_color: [0.4, 0.4, 0.4, 1.0],
-            grid_border_width: 3.0,
-            cell_border_width: 1.0,
-            cell_unsolved_hint_text_color: [0.0, 0.0, 0.0, 1.0],
-            cell_unsolved_background_color: [1.0; 4],
-            cell_solved_shaded_hint_text_color: [1.0; 4],
-            cell_solved_shaded_background_color: [0.0, 0.0, 0.0, 1.0],
-            cell_solved_unshaded_hint_text_color: [0.5, 0.5, 0.5, 1.0],
-            cell_solved_unshaded_background_color: [0.9, 0.9, 0.9, 1.0],
-            cell_current_color: [1.0, 0.0, 0.0, 1.0],
-        }
-    }
-}
-
 /// Stores visual information about a picture grid
 pub struct PictureGridView {
     /// Stores picgrid view settings.
@@ -79,15 +23,18 @@ pub struct PictureGridView {
     pub cell_size: f64,
     /// Calculated position/size of grid
     pub grid_rect: [f64; 4],
+    /// Calculated area of the window the grid is drawn in (the grid is clipped to it)
+    pub grid_area: [f64; 4],
 }
 
 impl PictureGridView {
     /// Creates a new picgrid view.
     pub fn new(settings: PictureGridViewSettings) -> PictureGridView {
         PictureGridView {
-            settings: settings,
+            settings,
             cell_size: 1.0,
             grid_rect: [1.0; 4],
+            grid_area: [1.0; 4],
         }
     }
 
@@ -102,34 +49,61 @@ impl PictureGridView {
         C: CharacterCache,
         G: Graphics<Texture = <C as CharacterCache>::Texture>,
     {
-        let ref settings = self.settings;
-        self.cell_size = settings.cell_size;
+        let settings = &self.settings;
+        let mut fitted_cell_size = settings.cell_size;
+        let mut draw_state = c.draw_state;
         if let Some(vp) = c.viewport {
-            let hcell = ((vp.rect[2] as f64) - settings.margin[0] - settings.grid_position[0])
+            self.grid_area = [
+                settings.grid_position[0],
+                settings.grid_position[1],
+                (vp.window_size[0] as f64) - settings.margin[0] - settings.grid_position[0],
+                (vp.window_size[1] as f64) - settings.margin[1] - settings.grid_position[1],
+            ];
+            let hcell = (self.grid_area[2] - settings.grid_border_width)
                 / (controller.picgrid.width as f64);
-            let vcell = ((vp.rect[3] as f64) - (settings.margin[1] * 2.0))
+            let vcell = (self.grid_area[3] - settings.grid_border_width)
                 / (controller.picgrid.height as f64);
+            fitted_cell_size = f64::min(hcell, vcell);
 
-            self.cell_size = f64::min(hcell, vcell);
+            // clip the zoomed/panned grid to its area (the scissor is in frame buffer pixels)
+            let scale = if vp.window_size[0] > 0 {
+                f64::from(vp.draw_size[0]) / f64::from(vp.window_size[0])
+            } else {
+                1.0
+            };
+            let area = self.grid_area;
+            draw_state = draw_state.scissor([
+                (area[0] * scale) as u32,
+                (area[1] * scale) as u32,
+                (area[2].max(0.0) * scale) as u32,
+                (area[3].max(0.0) * scale) as u32,
+            ]);
         }
 
+        self.cell_size = fitted_cell_size * controller.zoom;
         self.grid_rect = [
-            settings.grid_position[0],
-            settings.grid_position[1],
+            settings.grid_position[0] + controller.pan[0],
+            settings.grid_position[1] + controller.pan[1],
             (self.cell_size * f64::from(controller.picgrid.width)) + settings.grid_border_width,
             (self.cell_size * f64::from(controller.picgrid.height)) + settings.grid_border_width,
         ];
+        if c.viewport.is_none() {
+            self.grid_area = self.grid_rect;
+        }
+        let draw_state = &draw_state;
 
         // outer grid border
-        Rectangle::new_border(
-            settings.grid_border_color,
-            (settings.grid_border_width / 2.0).into(),
-        ).draw(self.grid_rect, &c.draw_state, c.transform, g);
+        Rectangle::new_border(settings.grid_border_color, settings.grid_border_width / 2.0).draw(
+            self.grid_rect,
+            draw_state,
+            c.transform,
+            g,
+        );
 
         // grid cells
         let cell_border = Border {
             color: settings.grid_border_color,
-            radius: (settings.cell_border_width / 2.0).into(),
+            radius: settings.cell_border_width / 2.0,
         };
         let cell_unsolved =
             Rectangle::new(settings.cell_unsolved_background_color).border(cell_border);
@@ -142,53 +116,95 @@ impl PictureGridView {
             settings.cell_border_width,
         );
         let current_cell = Rectangle::new(settings.cell_current_color);
+        let violated_cell = Rectangle::new_border(
+            settings.cell_violation_color,
+            settings.grid_border_width / 2.0,
+        );
+        let violations: HashSet<isize> = controller
+            .picgrid
+            .validate()
+            .iter()
+            .map(|violation| violation.y * controller.picgrid.width as isize + violation.x)
+            .collect();
         let mut cell_rect = [0.0, 0.0, self.cell_size, self.cell_size];
         let grid_origin = [
-            self.grid_rect[0] + f64::from(settings.grid_border_width / 2.0),
-            self.grid_rect[1] + f64::from(settings.grid_border_width / 2.0),
+            self.grid_rect[0] + (settings.grid_border_width / 2.0),
+            self.grid_rect[1] + (settings.grid_border_width / 2.0),
         ];
-        let cell_hint_text_size = (self.cell_size * 0.75) as u32;
+        let (hint_size, hint_offset) = hint_text_layout(self.cell_size);
+        let cell_hint_text_size = hint_size as u32;
 
-        for (index, state) in controller.picgrid.cells.iter() {
-            let column_ptr: u16 = (*index % (controller.picgrid.width as isize)) as u16;
-            let row_ptr: u16 = ((*index as u16) - column_ptr) / controller.picgrid.width;
+        for (index, state) in controller.picgrid.cells.iter().enumerate() {
+            let column_ptr: u16 = (index % (controller.picgrid.width as usize)) as u16;
+            let row_ptr: u16 = (index / (controller.picgrid.width as usize)) as u16;
 
             cell_rect[0] = grid_origin[0] + (f64::from(column_ptr) * self.cell_size);
             cell_rect[1] = grid_origin[1] + (f64::from(row_ptr) * self.cell_size);
 
+            // skip cells zoomed or panned out of the grid area
+            let area = self.grid_area;
+            if cell_rect[0] + cell_rect[2] < area[0]
+                || cell_rect[1] + cell_rect[3] < area[1]
+                || cell_rect[0] > area[0] + area[2]
+                || cell_rect[1] > area[1] + area[3]
+            {
+                continue;
+            }
+
+            let is_violated = violations.contains(&(index as isize));
+            let is_complete = state.hint() < PictureGrid::EMPTY
+                && controller
+                    .picgrid
+                    .is_complete(column_ptr as isize, row_ptr as isize);
+            let hint_text_color = |color: Color| {
+                if is_violated {
+                    settings.cell_violation_color
+                } else if is_complete {
+                    // clues that need nothing more are dimmed
+                    [
+                        color[0],
+                        color[1],
+                        color[2],
+                        color[3] * settings.complete_hint_text_opacity,
+                    ]
+                } else {
+                    color
+                }
+            };
+
             let text_transform = c.transform.trans(
-                cell_rect[0] + (self.cell_size * 0.30),
-                cell_rect[1] + (self.cell_size * 0.75),
+                cell_rect[0] + hint_offset[0],
+                cell_rect[1] + hint_offset[1],
             );
 
             match state {
                 CellState::Unsolved(value) => {
-                    cell_unsolved.draw(cell_rect, &c.draw_state, c.transform, g);
+                    cell_unsolved.draw(cell_rect, draw_state, c.transform, g);
                     if *value < PictureGrid::EMPTY {
                         Text::new_color(
-                            settings.cell_unsolved_hint_text_color,
+                            hint_text_color(settings.cell_unsolved_hint_text_color),
                             cell_hint_text_size,
-                        ).draw(&value.to_string(), glyphs, &c.draw_state, text_transform, g)
+                        ).draw(&value.to_string(), glyphs, draw_state, text_transform, g)
                         .ok();
                     }
                 }
                 CellState::Shaded(value) => {
-                    cell_solved_shaded.draw(cell_rect, &c.draw_state, c.transform, g);
+                    cell_solved_shaded.draw(cell_rect, draw_state, c.transform, g);
                     if *value < PictureGrid::EMPTY {
                         Text::new_color(
-                            settings.cell_solved_shaded_hint_text_color,
+                            hint_text_color(settings.cell_solved_shaded_hint_text_color),
                             cell_hint_text_size,
-                        ).draw(&value.to_string(), glyphs, &c.draw_state, text_transform, g)
+                        ).draw(&value.to_string(), glyphs, draw_state, text_transform, g)
                         .ok();
                     }
                 }
                 CellState::Unshaded(value) => {
-                    cell_solved_unshaded.draw(cell_rect, &c.draw_state, c.transform, g);
+                    cell_solved_unshaded.draw(cell_rect, draw_state, c.transform, g);
                     if *value < PictureGrid::EMPTY {
                         Text::new_color(
-                            settings.cell_solved_unshaded_hint_text_color,
+                            hint_text_color(settings.cell_solved_unshaded_hint_text_color),
                             cell_hint_text_size,
-                        ).draw(&value.to_string(), glyphs, &c.draw_state, text_transform, g)
+                        ).draw(&value.to_string(), glyphs, draw_state, text_transform, g)
                         .ok();
                     }
 
@@ -200,29 +216,141 @@ impl PictureGridView {
                     let bottom = cell_rect[1] + cell_rect[3] - (cell_rect[3] * padding_percent);
                     cell_solved_unshaded_x.draw(
                         [left, top, right, bottom],
-                        &c.draw_state,
+                        draw_state,
                         c.transform,
                         g,
                     );
                     cell_solved_unshaded_x.draw(
                         [left, bottom, right, top],
-                        &c.draw_state,
+                        draw_state,
                         c.transform,
                         g,
                     );
                 }
             }
 
+            if is_violated {
+                let border = settings.grid_border_width / 2.0;
+                violated_cell.draw(
+                    [
+                        cell_rect[0] + border,
+                        cell_rect[1] + border,
+                        cell_rect[2] - (border * 2.0),
+                        cell_rect[3] - (border * 2.0),
+                    ],
+                    draw_state,
+                    c.transform,
+                    g,
+                );
+            }
+
             if let Some(pos) = &controller.cell_pos {
                 if controller.is_solving
                     && (pos[0] - 1 == column_ptr as isize)
                     && (pos[1] == row_ptr as isize)
                 {
-                    current_cell.draw(cell_rect, &c.draw_state, c.transform, g);
+                    current_cell.draw(cell_rect, draw_state, c.transform, g);
                 }
             }
         }
 
+        // cells that differ from the solution (found by a check, or placed in strict mode)
+        let wrong_border = settings.grid_border_width * 0.75;
+        let wrong_cell = Rectangle::new_border(settings.cell_wrong_color, wrong_border);
+        for pos in controller.marked_wrong_cells() {
+            wrong_cell.draw(
+                [
+                    grid_origin[0] + (pos[0] as f64 * self.cell_size) + wrong_border,
+                    grid_origin[1] + (pos[1] as f64 * self.cell_size) + wrong_border,
+                    self.cell_size - (wrong_border * 2.0),
+                    self.cell_size - (wrong_border * 2.0),
+                ],
+                draw_state,
+                c.transform,
+                g,
+            );
+        }
+
+        // 3x3 area of the hovered cell
+        if let Some(pos) = controller.cell_pos {
+            if !controller.is_solving {
+                let first = [(pos[0] - 1).max(0), (pos[1] - 1).max(0)];
+                let last = [
+                    (pos[0] + 1).min(controller.picgrid.width as isize - 1),
+                    (pos[1] + 1).min(controller.picgrid.height as isize - 1),
+                ];
+                let border = settings.grid_border_width / 2.0;
+                Rectangle::new_border(settings.cell_neighborhood_color, border).draw(
+                    [
+                        grid_origin[0] + (first[0] as f64 * self.cell_size),
+                        grid_origin[1] + (first[1] as f64 * self.cell_size),
+                        (last[0] - first[0] + 1) as f64 * self.cell_size,
+                        (last[1] - first[1] + 1) as f64 * self.cell_size,
+                    ],
+                    draw_state,
+                    c.transform,
+                    g,
+                );
+            }
+        }
+
+        // hint: the responsible clues and (the state of) the cells they force
+        if let Some(ref deduction) = controller.hint {
+            let border = settings.grid_border_width / 2.0;
+            let clue_outline = Rectangle::new_border(settings.cell_hint_color, border);
+            for pos in deduction.clues.iter() {
+                clue_outline.draw(
+                    [
+                        grid_origin[0] + (pos[0] as f64 * self.cell_size) + border,
+                        grid_origin[1] + (pos[1] as f64 * self.cell_size) + border,
+                        self.cell_size - (border * 2.0),
+                        self.cell_size - (border * 2.0),
+                    ],
+                    draw_state,
+                    c.transform,
+                    g,
+                );
+            }
+            let inset = self.cell_size * 0.3;
+            for (pos, state) in deduction.cells.iter() {
+                let color = match state {
+                    CellState::Shaded(_hint) => settings.cell_solved_shaded_background_color,
+                    _ => settings.cell_solved_unshaded_background_color,
+                };
+                let forced_cell = Rectangle::new(color).border(Border {
+                    color: settings.cell_hint_color,
+                    radius: border,
+                });
+                forced_cell.draw(
+                    [
+                        grid_origin[0] + (pos[0] as f64 * self.cell_size) + inset,
+                        grid_origin[1] + (pos[1] as f64 * self.cell_size) + inset,
+                        self.cell_size - (inset * 2.0),
+                        self.cell_size - (inset * 2.0),
+                    ],
+                    draw_state,
+                    c.transform,
+                    g,
+                );
+            }
+        }
+
+        // keyboard cursor
+        if let Some(pos) = controller.key_cursor {
+            let border = settings.grid_border_width / 2.0;
+            Rectangle::new_border(settings.cell_cursor_color, border).draw(
+                [
+                    grid_origin[0] + (pos[0] as f64 * self.cell_size) + border,
+                    grid_origin[1] + (pos[1] as f64 * self.cell_size) + border,
+                    self.cell_size - (border * 2.0),
+                    self.cell_size - (border * 2.0),
+                ],
+                draw_state,
+                c.transform,
+                g,
+            );
+        }
+
         let labels = controller.get_messages();
         // labels.push("".to_string());
         // labels.push(format!("Cell Size: {:?}", &self.cell_size));
@@ -240,7 +368,7 @@ impl PictureGridView {
         for label in labels.iter() {
             label_graphic
                 .draw(
-                    &label,
+                    label,
                     glyphs,
                     &c.draw_state,
                     c.transform.trans(
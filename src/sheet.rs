@@ -0,0 +1,551 @@
+//! Printable puzzle sheets (SVG or PDF).
+
+use std::fs::File;
+use std::io;
+use std::io::Write;
+use std::path::Path;
+
+use picgrid::{CellState, PictureGrid};
+use view_settings::hint_text_layout;
+
+/// Enumeration of sheet formats
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SheetFormat {
+    /// Scalable Vector Graphics (the answer key is drawn below the puzzle)
+    Svg,
+    /// Portable Document Format (the answer key is on its own page)
+    Pdf,
+}
+
+impl SheetFormat {
+    /// Finds the format from the file extension (`.svg` or `.pdf`)
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Option<SheetFormat> {
+        let extension = path.as_ref().extension()?.to_str()?.to_lowercase();
+        match extension.as_str() {
+            "svg" => Some(SheetFormat::Svg),
+            "pdf" => Some(SheetFormat::Pdf),
+            _ => None,
+        }
+    }
+}
+
+/// A4 page size in points
+pub const PAGE_A4: [f64; 2] = [595.0, 842.0];
+/// US Letter page size in points
+pub const PAGE_LETTER: [f64; 2] = [612.0, 792.0];
+
+/// Size of the title text in points
+const TITLE_SIZE: f64 = 14.0;
+/// Width of the lines between cells in points
+const CELL_LINE_WIDTH: f64 = 0.5;
+/// Width of the line around the grid in points
+const GRID_LINE_WIDTH: f64 = 2.0;
+/// Smallest cell size in points a grid is fitted with
+const MIN_CELL_SIZE: f64 = 1.0;
+/// Gray level of shaded cells in the answer key (0 is black, 1 is white)
+const ANSWER_SHADED_GRAY: f64 = 0.2;
+
+/// Drawing operations shared by the SVG and PDF output (y grows downwards from the page top).
+trait SheetCanvas {
+    /// Draws a rectangle, filled with a gray level and/or outlined in black
+    fn rect(&mut self, rect: [f64; 4], fill: Option<f64>, line_width: Option<f64>);
+    /// Draws text starting at (x,y) on the baseline
+    fn text(&mut self, x: f64, y: f64, size: f64, gray: f64, text: &str);
+}
+
+/// Collects SVG elements of stacked pages
+struct SvgCanvas {
+    elements: String,
+    /// Offset of the current page from the top of the document
+    page_top: f64,
+}
+
+impl SheetCanvas for SvgCanvas {
+    fn rect(&mut self, rect: [f64; 4], fill: Option<f64>, line_width: Option<f64>) {
+        let fill = match fill {
+            Some(gray) => svg_gray(gray),
+            None => "none".to_string(),
+        };
+        let stroke = match line_width {
+            Some(width) => format!(" stroke=\"#000000\" stroke-width=\"{}\"", width),
+            None => String::new(),
+        };
+        self.elements.push_str(&format!(
+            "<rect x=\"{:.2}\" y=\"{:.2}\" width=\"{:.2}\" height=\"{:.2}\" fill=\"{}\"{}/>\n",
+            rect[0],
+            self.page_top + rect[1],
+            rect[2],
+            rect[3],
+            fill,
+            stroke
+        ));
+    }
+
+    fn text(&mut self, x: f64, y: f64, size: f64, gray: f64, text: &str) {
+        self.elements.push_str(&format!(
+            "<text x=\"{:.2}\" y=\"{:.2}\" font-family=\"Helvetica, Arial, sans-serif\" \
+             font-size=\"{:.2}\" fill=\"{}\">{}</text>\n",
+            x,
+            self.page_top + y,
+            size,
+            svg_gray(gray),
+            text.replace('&', "&amp;").replace('<', "&lt;")
+        ));
+    }
+}
+
+/// Formats a gray level as an SVG color
+fn svg_gray(gray: f64) -> String {
+    let level = (gray.clamp(0.0, 1.0) * 255.0).round() as u8;
+    format!("#{:02x}{:02x}{:02x}", level, level, level)
+}
+
+/// Collects the PDF content stream of a page
+struct PdfCanvas {
+    content: String,
+    page_height: f64,
+}
+
+impl SheetCanvas for PdfCanvas {
+    fn rect(&mut self, rect: [f64; 4], fill: Option<f64>, line_width: Option<f64>) {
+        // PDF coordinates start at the bottom of the page
+        let bottom = self.page_height - rect[1] - rect[3];
+        let path = format!(
+            "{:.2} {:.2} {:.2} {:.2} re",
+            rect[0], bottom, rect[2], rect[3]
+        );
+        if let Some(gray) = fill {
+            self.content
+                .push_str(&format!("{:.3} g {} f\n", gray.clamp(0.0, 1.0), path));
+        }
+        if let Some(width) = line_width {
+            self.content
+                .push_str(&format!("0 G {:.2} w {} S\n", width, path));
+        }
+    }
+
+    fn text(&mut self, x: f64, y: f64, size: f64, gray: f64, text: &str) {
+        self.content.push_str(&format!(
+            "BT {:.3} g /F1 {:.2} Tf {:.2} {:.2} Td ({}) Tj ET\n",
+            gray.clamp(0.0, 1.0),
+            size,
+            x,
+            self.page_height - y,
+            pdf_string(text)
+        ));
+    }
+}
+
+/// Escapes text for a PDF string in the font's WinAnsiEncoding.
+///
+/// Characters outside of ASCII are written as octal escapes, those the encoding lacks as `?`.
+fn pdf_string(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' | '(' | ')' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            ' '..='~' => escaped.push(c),
+            _ => match win_ansi_byte(c) {
+                Some(byte) => escaped.push_str(&format!("\\{:03o}", byte)),
+                None => escaped.push('?'),
+            },
+        }
+    }
+    escaped
+}
+
+/// Finds the WinAnsiEncoding byte of a character outside of ASCII (Latin-1 and common
+/// punctuation)
+fn win_ansi_byte(c: char) -> Option<u8> {
+    match c {
+        '\u{a0}'..='\u{ff}' => Some(c as u32 as u8),
+        '€' => Some(0x80),
+        '…' => Some(0x85),
+        '‘' => Some(0x91),
+        '’' => Some(0x92),
+        '“' => Some(0x93),
+        '”' => Some(0x94),
+        '•' => Some(0x95),
+        '–' => Some(0x96),
+        '—' => Some(0x97),
+        _ => None,
+    }
+}
+
+/// Lays out a puzzle (and optionally its answer key) on printable pages.
+pub struct PuzzleSheet<'a> {
+    /// Puzzle to print (cell states are ignored, every cell is drawn unsolved)
+    picgrid: &'a PictureGrid,
+    /// Solved grid drawn on the second page (if any)
+    answers: Option<&'a PictureGrid>,
+    /// Title printed above the grid
+    title: String,
+    /// (w,h) page size in points
+    page_size: [f64; 2],
+    /// Page margin on every side in points
+    margin: f64,
+    /// Width/height of grid cells in points (None fits the grid to the page)
+    cell_size: Option<f64>,
+}
+
+impl<'a> PuzzleSheet<'a> {
+    /// Creates a new A4 sheet with 36pt (half inch) margins and cells fitted to the page.
+    pub fn new(picgrid: &'a PictureGrid) -> PuzzleSheet<'a> {
+        PuzzleSheet {
+            picgrid,
+            answers: None,
+            title: String::new(),
+            page_size: PAGE_A4,
+            margin: 36.0,
+            cell_size: None,
+        }
+    }
+
+    /// Sets the solved grid to print as an answer key on a second page
+    pub fn with_answers(mut self, answers: Option<&'a PictureGrid>) -> Self {
+        self.answers = answers;
+        self
+    }
+
+    /// Sets the title printed above the grid
+    pub fn with_title(mut self, title: &str) -> Self {
+        self.title = title.to_string();
+        self
+    }
+
+    /// Sets the (w,h) page size in points
+    pub fn with_page_size(mut self, page_size: [f64; 2]) -> Self {
+        self.page_size = page_size;
+        self
+    }
+
+    /// Sets the page margin in points
+    pub fn with_margin(mut self, margin: f64) -> Self {
+        self.margin = margin.max(0.0);
+        self
+    }
+
+    /// Sets the width/height of grid cells in points (None fits the grid to the page)
+    pub fn with_cell_size(mut self, cell_size: Option<f64>) -> Self {
+        self.cell_size = cell_size;
+        self
+    }
+
+    /// Returns the number of pages (two with an answer key)
+    pub fn num_pages(&self) -> usize {
+        if self.answers.is_some() {
+            2
+        } else {
+            1
+        }
+    }
+
+    /// Finds the height used by the title (none without a title)
+    fn title_height(&self) -> f64 {
+        if self.title.is_empty() {
+            0.0
+        } else {
+            TITLE_SIZE * 2.0
+        }
+    }
+
+    /// Finds the largest cell size that fits the grid within the margins
+    fn max_cell_size(&self) -> f64 {
+        let width = self.page_size[0] - self.margin * 2.0 - GRID_LINE_WIDTH;
+        let height = self.page_size[1] - self.margin * 2.0 - self.title_height() - GRID_LINE_WIDTH;
+        f64::min(
+            width / f64::from(self.picgrid.width),
+            height / f64::from(self.picgrid.height),
+        )
+    }
+
+    /// Finds the cell size (the configured one, or the largest that fits within the margins).
+    ///
+    /// Fails if the grid does not fit within the margins, with the configured cell size or
+    /// (when fitted) with cells of at least `MIN_CELL_SIZE`.
+    pub fn cell_size(&self) -> io::Result<f64> {
+        let max_cell_size = self.max_cell_size();
+        let cell_size = match self.cell_size {
+            Some(cell_size) => cell_size,
+            None => max_cell_size.max(MIN_CELL_SIZE),
+        };
+        // allow for rounding in the fitted size
+        if cell_size <= max_cell_size * (1.0 + 1e-9) {
+            Ok(cell_size)
+        } else {
+            Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "{}x{} grid with {}pt cells does not fit on the page (cells of at most {:.2}pt do)",
+                    self.picgrid.width,
+                    self.picgrid.height,
+                    cell_size,
+                    max_cell_size.max(0.0)
+                ),
+            ))
+        }
+    }
+
+    /// Draws a page: the clue grid, with the solution of `answers` when given
+    fn draw_page(
+        &self,
+        canvas: &mut dyn SheetCanvas,
+        answers: Option<&PictureGrid>,
+        cell_size: f64,
+    ) {
+        let grid_width = cell_size * f64::from(self.picgrid.width);
+        let grid_height = cell_size * f64::from(self.picgrid.height);
+        // centered horizontally, below the title
+        let left = (self.page_size[0] - grid_width) / 2.0;
+        let top = self.margin + self.title_height();
+
+        if !self.title.is_empty() {
+            let title = match answers {
+                Some(_answers) => format!("{} (answer key)", self.title),
+                None => self.title.clone(),
+            };
+            canvas.text(
+                self.margin,
+                self.margin + TITLE_SIZE,
+                TITLE_SIZE,
+                0.0,
+                &title,
+            );
+        }
+
+        let (hint_size, hint_offset) = hint_text_layout(cell_size);
+        for y in 0..self.picgrid.height as isize {
+            for x in 0..self.picgrid.width as isize {
+                let hint = match self.picgrid.get(x, y) {
+                    Some(cell) => cell.hint(),
+                    None => continue,
+                };
+                let shaded = matches!(
+                    answers.and_then(|answers| answers.get(x, y)),
+                    Some(CellState::Shaded(_hint))
+                );
+                let cell_rect = [
+                    left + cell_size * x as f64,
+                    top + cell_size * y as f64,
+                    cell_size,
+                    cell_size,
+                ];
+                let fill = if shaded { ANSWER_SHADED_GRAY } else { 1.0 };
+                canvas.rect(cell_rect, Some(fill), Some(CELL_LINE_WIDTH));
+                if hint < PictureGrid::EMPTY {
+                    canvas.text(
+                        cell_rect[0] + hint_offset[0],
+                        cell_rect[1] + hint_offset[1],
+                        hint_size,
+                        if shaded { 1.0 } else { 0.0 },
+                        &hint.to_string(),
+                    );
+                }
+            }
+        }
+        canvas.rect(
+            [left, top, grid_width, grid_height],
+            None,
+            Some(GRID_LINE_WIDTH),
+        );
+    }
+
+    /// Renders the sheet as SVG, pages stacked from top to bottom (see `cell_size` for when
+    /// it fails)
+    pub fn to_svg(&self) -> io::Result<String> {
+        let cell_size = self.cell_size()?;
+        let mut canvas = SvgCanvas {
+            elements: String::new(),
+            page_top: 0.0,
+        };
+        self.draw_page(&mut canvas, None, cell_size);
+        if let Some(answers) = self.answers {
+            canvas.page_top = self.page_size[1];
+            self.draw_page(&mut canvas, Some(answers), cell_size);
+        }
+
+        let (width, height) = (
+            self.page_size[0],
+            self.page_size[1] * self.num_pages() as f64,
+        );
+        Ok(format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}pt\" height=\"{}pt\" \
+             viewBox=\"0 0 {} {}\">\n<rect width=\"{}\" height=\"{}\" fill=\"#ffffff\"/>\n{}</svg>\n",
+            width, height, width, height, width, height, canvas.elements
+        ))
+    }
+
+    /// Renders the sheet as PDF, one page per grid (see `cell_size` for when it fails)
+    pub fn to_pdf(&self) -> io::Result<Vec<u8>> {
+        let cell_size = self.cell_size()?;
+        let mut contents = Vec::new();
+        for page in 0..self.num_pages() {
+            let mut canvas = PdfCanvas {
+                content: String::new(),
+                page_height: self.page_size[1],
+            };
+            let answers = if page == 0 { None } else { self.answers };
+            self.draw_page(&mut canvas, answers, cell_size);
+            contents.push(canvas.content);
+        }
+
+        // objects: 1 catalog, 2 page tree, 3 font, then a page and its content per page
+        let page_ids: Vec<usize> = (0..contents.len()).map(|page| 4 + page * 2).collect();
+        let mut objects = vec![
+            "<< /Type /Catalog /Pages 2 0 R >>".to_string(),
+            format!(
+                "<< /Type /Pages /Kids [{}] /Count {} >>",
+                page_ids
+                    .iter()
+                    .map(|id| format!("{} 0 R", id))
+                    .collect::<Vec<String>>()
+                    .join(" "),
+                page_ids.len()
+            ),
+            "<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica /Encoding /WinAnsiEncoding >>"
+                .to_string(),
+        ];
+        for (content, page_id) in contents.iter().zip(page_ids.iter()) {
+            objects.push(format!(
+                "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {} {}] \
+                 /Resources << /Font << /F1 3 0 R >> >> /Contents {} 0 R >>",
+                self.page_size[0],
+                self.page_size[1],
+                page_id + 1
+            ));
+            objects.push(format!(
+                "<< /Length {} >>\nstream\n{}endstream",
+                content.len(),
+                content
+            ));
+        }
+
+        let mut pdf = b"%PDF-1.4\n".to_vec();
+        let mut offsets = Vec::with_capacity(objects.len());
+        for (index, object) in objects.iter().enumerate() {
+            offsets.push(pdf.len());
+            pdf.extend_from_slice(format!("{} 0 obj\n{}\nendobj\n", index + 1, object).as_bytes());
+        }
+        let xref_offset = pdf.len();
+        pdf.extend_from_slice(
+            format!("xref\n0 {}\n0000000000 65535 f \n", objects.len() + 1).as_bytes(),
+        );
+        for offset in offsets {
+            pdf.extend_from_slice(format!("{:010} 00000 n \n", offset).as_bytes());
+        }
+        pdf.extend_from_slice(
+            format!(
+                "trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{}\n%%EOF\n",
+                objects.len() + 1,
+                xref_offset
+            )
+            .as_bytes(),
+        );
+        Ok(pdf)
+    }
+
+    /// Writes the sheet to a file, in the format of its extension (`.svg` or `.pdf`).
+    ///
+    /// Fails without writing if the grid does not fit on the page (see `cell_size`).
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let bytes = match SheetFormat::from_path(&path) {
+            Some(SheetFormat::Svg) => self.to_svg()?.into_bytes(),
+            Some(SheetFormat::Pdf) => self.to_pdf()?,
+            None => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "unsupported sheet format (expected .svg or .pdf)",
+                ))
+            }
+        };
+        File::create(path)?.write_all(&bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use text_format::parse_text;
+
+    /// Renders the sheet as PDF text (the output is ASCII)
+    fn pdf(sheet: &PuzzleSheet) -> String {
+        String::from_utf8(sheet.to_pdf().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn xref_offsets_point_at_objects() {
+        let picgrid = parse_text("1.\n..\n").unwrap();
+        let answers = parse_text("1.\n..\n\n#x\nxx\n").unwrap();
+        let pdf = pdf(&PuzzleSheet::new(&picgrid)
+            .with_title("small")
+            .with_answers(Some(&answers)));
+
+        let xref = pdf.find("\nxref\n").unwrap() + 1;
+        let startxref = pdf.find("startxref\n").unwrap() + "startxref\n".len();
+        assert_eq!(pdf[startxref..].lines().next().unwrap(), xref.to_string());
+
+        // catalog, page tree, font, then a page and its content per page
+        let entries: Vec<&str> = pdf[xref..].lines().skip(3).take(7).collect();
+        for (index, entry) in entries.iter().enumerate() {
+            let offset: usize = entry[..10].parse().unwrap();
+            let header = format!("{} 0 obj\n", index + 1);
+            assert!(pdf[offset..].starts_with(&header), "entry {}", index + 1);
+        }
+        assert!(pdf[xref..].starts_with("xref\n0 8\n"));
+    }
+
+    #[test]
+    fn answer_key_is_on_second_page() {
+        let picgrid = parse_text("1.\n..\n").unwrap();
+        let answers = parse_text("1.\n..\n\n#x\nxx\n").unwrap();
+        let shaded = format!("{:.3} g", ANSWER_SHADED_GRAY);
+
+        let puzzle = pdf(&PuzzleSheet::new(&picgrid));
+        assert!(puzzle.contains("/Count 1 >>"));
+        assert!(!puzzle.contains(&shaded));
+
+        let with_answers = pdf(&PuzzleSheet::new(&picgrid).with_answers(Some(&answers)));
+        assert!(with_answers.contains("/Count 2 >>"));
+        let streams: Vec<&str> = with_answers.split("endstream").collect();
+        assert_eq!(streams.len(), 3);
+        assert!(!streams[0].contains(&shaded));
+        assert_eq!(streams[1].matches(&shaded).count(), 1);
+    }
+
+    #[test]
+    fn escapes_title() {
+        let picgrid = parse_text("1\n").unwrap();
+        let pdf = pdf(&PuzzleSheet::new(&picgrid).with_title("a(b)\\c"));
+        assert!(pdf.contains("(a\\(b\\)\\\\c) Tj"));
+    }
+
+    #[test]
+    fn encodes_title_outside_of_ascii() {
+        let picgrid = parse_text("1\n").unwrap();
+        let pdf = pdf(&PuzzleSheet::new(&picgrid).with_title("Café–π"));
+        assert!(pdf.contains("(Caf\\351\\226?) Tj"));
+        assert!(pdf.contains("/Encoding /WinAnsiEncoding"));
+    }
+
+    #[test]
+    fn rejects_grid_wider_than_page() {
+        let picgrid = PictureGrid::new(10, 10);
+        let sheet = PuzzleSheet::new(&picgrid).with_cell_size(Some(100.0));
+        assert!(sheet.cell_size().is_err());
+        assert!(sheet.to_pdf().is_err());
+        assert!(sheet.to_svg().is_err());
+        let sheet = PuzzleSheet::new(&picgrid).with_cell_size(Some(20.0));
+        assert_eq!(sheet.cell_size().unwrap(), 20.0);
+
+        // fitted cells smaller than the minimum
+        let picgrid = PictureGrid::new(1000, 10);
+        let err = PuzzleSheet::new(&picgrid).cell_size().unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "1000x10 grid with 1pt cells does not fit on the page (cells of at most 0.52pt do)"
+        );
+    }
+}
@@ -0,0 +1,684 @@
+//! Window-free solving algorithm.
+
+use std::cmp;
+use std::fmt;
+
+use picgrid::{CellState, PictureGrid};
+
+/// Enumeration of deduction techniques
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Technique {
+    /// A single clue forces all of its remaining neighbors
+    SingleClue,
+    /// Two clues with overlapping neighborhoods force some of their neighbors
+    ClueOverlap,
+}
+
+impl fmt::Display for Technique {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Technique::SingleClue => write!(f, "single clue"),
+            Technique::ClueOverlap => write!(f, "clue overlap"),
+        }
+    }
+}
+
+/// A deduction found in a picture grid, ready to be applied.
+#[derive(Clone, Debug)]
+pub struct Deduction {
+    /// Technique that found the deduction
+    pub technique: Technique,
+    /// Positions of the clues responsible for the deduction
+    pub clues: Vec<[isize; 2]>,
+    /// Positions of the forced cells along with their new state
+    pub cells: Vec<([isize; 2], CellState)>,
+}
+
+impl Deduction {
+    /// Sets the state of every forced cell (hints are unchanged)
+    pub fn apply(&self, picgrid: &mut PictureGrid) {
+        for (pos, state) in self.cells.iter() {
+            picgrid.set_unsolved_state(pos[0], pos[1], *state);
+        }
+    }
+}
+
+/// Returns the unsolved cells in the surrounding grid of (x,y)
+fn unsolved_neighbors(picgrid: &PictureGrid, x: isize, y: isize) -> Vec<[isize; 2]> {
+    let mut neighbors = Vec::with_capacity(9);
+    for b in (y - 1)..(y + 2) {
+        for a in (x - 1)..(x + 2) {
+            if let Some(CellState::Unsolved(_hint)) = picgrid.get(a, b) {
+                neighbors.push([a, b]);
+            }
+        }
+    }
+    neighbors
+}
+
+/// Finds the deduction made by the single clue rules for the clue at (x,y).
+///
+/// If the hint equals the number of shaded neighbors, the remaining neighbors are
+/// unshaded. If the hint equals the number of shaded plus unsolved neighbors, the
+/// remaining neighbors are shaded.
+pub fn find_clue_deduction(picgrid: &PictureGrid, x: isize, y: isize) -> Option<Deduction> {
+    let cell_hint = picgrid.get(x, y)?.hint();
+    if cell_hint == PictureGrid::EMPTY || picgrid.is_complete(x, y) {
+        return None;
+    }
+    let cell_shaded = picgrid.num_shaded(x, y);
+    let cell_unsolved = picgrid.num_unsolved(x, y);
+    let state = if cell_hint == cell_shaded {
+        CellState::Unshaded(0)
+    } else if cell_hint == (cell_shaded + cell_unsolved) {
+        CellState::Shaded(0)
+    } else {
+        return None;
+    };
+    Some(Deduction {
+        technique: Technique::SingleClue,
+        clues: vec![[x, y]],
+        cells: unsolved_neighbors(picgrid, x, y)
+            .into_iter()
+            .map(|pos| (pos, state))
+            .collect(),
+    })
+}
+
+/// Applies the single clue rules to the clue at (x,y).
+///
+/// Returns true if either rule fired (see `find_clue_deduction`).
+pub fn apply_clue_rules(picgrid: &mut PictureGrid, x: isize, y: isize) -> bool {
+    match find_clue_deduction(picgrid, x, y) {
+        Some(deduction) => {
+            deduction.apply(picgrid);
+            true
+        }
+        None => false,
+    }
+}
+
+/// Returns the number of shaded cells the clue at (x,y) still needs (if it is an incomplete clue)
+fn remaining_shaded(picgrid: &PictureGrid, x: isize, y: isize) -> Option<u8> {
+    let cell_hint = picgrid.get(x, y)?.hint();
+    let cell_shaded = picgrid.num_shaded(x, y);
+    if cell_hint == PictureGrid::EMPTY || cell_shaded > cell_hint || picgrid.is_complete(x, y) {
+        return None;
+    }
+    Some(cell_hint - cell_shaded)
+}
+
+/// Applies the subset/superset reasoning to the clues at `a` and `b`.
+///
+/// The unsolved neighbors of the two clues are split into the cells only `a` sees, the
+/// cells both see and the cells only `b` sees. The number of shaded cells in the shared
+/// part is bounded by what each clue still needs, which can force the other parts (or
+/// the shared part itself) to be entirely shaded or unshaded.
+fn overlap_deduction(picgrid: &PictureGrid, a: [isize; 2], b: [isize; 2]) -> Option<Deduction> {
+    let a_remaining = remaining_shaded(picgrid, a[0], a[1])? as usize;
+    let b_remaining = remaining_shaded(picgrid, b[0], b[1])? as usize;
+    let a_unsolved = unsolved_neighbors(picgrid, a[0], a[1]);
+    let b_unsolved = unsolved_neighbors(picgrid, b[0], b[1]);
+
+    let (shared, a_only): (Vec<[isize; 2]>, Vec<[isize; 2]>) =
+        a_unsolved.iter().partition(|pos| b_unsolved.contains(pos));
+    let b_only: Vec<[isize; 2]> = b_unsolved
+        .into_iter()
+        .filter(|pos| !shared.contains(pos))
+        .collect();
+    if shared.is_empty() {
+        return None;
+    }
+
+    let min_shared = cmp::max(
+        a_remaining.saturating_sub(a_only.len()),
+        b_remaining.saturating_sub(b_only.len()),
+    );
+    let max_shared = cmp::min(shared.len(), cmp::min(a_remaining, b_remaining));
+    if min_shared > max_shared {
+        // contradiction, leave it to the search
+        return None;
+    }
+
+    let mut cells = Vec::new();
+    {
+        // forces a part when its number of shaded cells is bounded by [min, max]
+        let mut force = |part: &[[isize; 2]], min: usize, max: usize| {
+            if !part.is_empty() && max == 0 {
+                cells.extend(part.iter().map(|pos| (*pos, CellState::Unshaded(0))));
+            } else if !part.is_empty() && min == part.len() {
+                cells.extend(part.iter().map(|pos| (*pos, CellState::Shaded(0))));
+            }
+        };
+        force(&a_only, a_remaining - max_shared, a_remaining - min_shared);
+        force(&b_only, b_remaining - max_shared, b_remaining - min_shared);
+        force(&shared, min_shared, max_shared);
+    }
+
+    if cells.is_empty() {
+        None
+    } else {
+        Some(Deduction {
+            technique: Technique::ClueOverlap,
+            clues: vec![a, b],
+            cells,
+        })
+    }
+}
+
+/// Finds the first deduction made by comparing two clues with overlapping neighborhoods.
+///
+/// Clues are visited in row-major order, each paired with the clues up to two cells away.
+pub fn find_overlap_deduction(picgrid: &PictureGrid) -> Option<Deduction> {
+    for y in 0..picgrid.height as isize {
+        for x in 0..picgrid.width as isize {
+            if remaining_shaded(picgrid, x, y).is_none() {
+                continue;
+            }
+            for dy in 0..3 {
+                for dx in -2..3 {
+                    if dy == 0 && dx <= 0 {
+                        continue;
+                    }
+                    let (bx, by) = (x + dx, y + dy);
+                    if bx < 0 || bx >= picgrid.width as isize || by >= picgrid.height as isize {
+                        continue;
+                    }
+                    if let Some(deduction) = overlap_deduction(picgrid, [x, y], [bx, by]) {
+                        return Some(deduction);
+                    }
+                }
+            }
+        }
+    }
+    None
+}
+
+/// Finds a deduction anywhere in the grid without applying it.
+///
+/// The single clue rules are tried first (clues in row-major order), then the clue overlap rule.
+pub fn find_deduction(picgrid: &PictureGrid) -> Option<Deduction> {
+    for y in 0..picgrid.height as isize {
+        for x in 0..picgrid.width as isize {
+            if let Some(deduction) = find_clue_deduction(picgrid, x, y) {
+                return Some(deduction);
+            }
+        }
+    }
+    find_overlap_deduction(picgrid)
+}
+
+/// Incremental state of the pass-based solving algorithm.
+///
+/// Each step visits the next incomplete clue (in row-major order) and applies the
+/// single clue rules to it. When a full pass makes no progress, a single clue overlap
+/// deduction is applied and another pass starts. Once neither makes progress the sweep
+/// stops.
+pub struct Sweep {
+    /// Next cell position to examine (if still solving)
+    pub cell_pos: Option<[isize; 2]>,
+    /// Stores if solving algorithm needs another pass
+    needs_pass: bool,
+    /// Number of completed passes
+    passes: u32,
+    /// Number of clue overlap deductions applied
+    overlaps: u32,
+    /// Stores the technique that most recently made progress
+    last_technique: Option<Technique>,
+}
+
+impl Default for Sweep {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Sweep {
+    /// Creates a new sweep starting at the upper left cell.
+    pub fn new() -> Sweep {
+        Sweep {
+            cell_pos: Some([0, 0]),
+            needs_pass: false,
+            passes: 0,
+            overlaps: 0,
+            last_technique: None,
+        }
+    }
+
+    /// Returns if the sweep still has work to do
+    pub fn is_active(&self) -> bool {
+        self.cell_pos.is_some()
+    }
+
+    /// Returns the number of completed passes
+    pub fn passes(&self) -> u32 {
+        self.passes
+    }
+
+    /// Returns the number of clue overlap deductions applied
+    pub fn overlaps(&self) -> u32 {
+        self.overlaps
+    }
+
+    /// Returns the technique that most recently made progress (if any)
+    pub fn last_technique(&self) -> Option<Technique> {
+        self.last_technique
+    }
+
+    /// Performs a single step of the algorithm.
+    ///
+    /// Returns true while the sweep is still active.
+    pub fn step(&mut self, picgrid: &mut PictureGrid) -> bool {
+        self.next_deduction(picgrid);
+        self.is_active()
+    }
+
+    /// Performs a single step of the algorithm.
+    ///
+    /// Returns the deduction applied by the step (if any).
+    pub fn next_deduction(&mut self, picgrid: &mut PictureGrid) -> Option<Deduction> {
+        let pos = self.cell_pos?;
+
+        let mut applied = None;
+        let (mut x, mut y, ncell) = picgrid.next_incomplete(pos[0], pos[1]);
+        if ncell.is_some() {
+            if let Some(deduction) = find_clue_deduction(picgrid, x, y) {
+                deduction.apply(picgrid);
+                self.needs_pass = true;
+                self.last_technique = Some(Technique::SingleClue);
+                applied = Some(deduction);
+            }
+        } else {
+            // nothing left in this pass
+            x = picgrid.width as isize;
+            y = picgrid.height as isize;
+        }
+
+        x += 1;
+        if x >= picgrid.width as isize {
+            x = 0;
+            y += 1;
+        }
+        if y < picgrid.height as isize {
+            self.cell_pos = Some([x, y]);
+        } else if self.needs_pass {
+            self.cell_pos = Some([0, 0]);
+            self.needs_pass = false;
+            self.passes += 1;
+        } else if let Some(deduction) = find_overlap_deduction(picgrid) {
+            deduction.apply(picgrid);
+            self.cell_pos = Some([0, 0]);
+            self.passes += 1;
+            self.overlaps += 1;
+            self.last_technique = Some(Technique::ClueOverlap);
+            applied = Some(deduction);
+        } else {
+            self.cell_pos = None;
+            self.passes += 1;
+        }
+        applied
+    }
+}
+
+/// Runs the sweep on the grid until a full pass makes no progress.
+///
+/// Returns the finished sweep, which holds the number of passes made.
+pub fn propagate(picgrid: &mut PictureGrid) -> Sweep {
+    let mut sweep = Sweep::new();
+    while sweep.step(picgrid) {}
+    sweep
+}
+
+/// Finds if any clue can no longer be satisfied (see `PictureGrid::validate`).
+pub fn has_contradiction(picgrid: &PictureGrid) -> bool {
+    for y in 0..picgrid.height as isize {
+        for x in 0..picgrid.width as isize {
+            if picgrid.clue_violation(x, y).is_some() {
+                return true;
+            }
+        }
+    }
+    false
+}
+
+/// Picks the unsolved cell to guess next.
+///
+/// Prefers a neighbor of the clue with the fewest unsolved neighbors, since a wrong
+/// guess there is found out soonest. Falls back to the first unsolved cell not covered
+/// by any clue.
+fn branch_cell(picgrid: &PictureGrid) -> Option<[isize; 2]> {
+    let mut best: Option<([isize; 2], u8)> = None;
+    let mut uncovered: Option<[isize; 2]> = None;
+    for y in 0..picgrid.height as isize {
+        for x in 0..picgrid.width as isize {
+            if let Some(cell) = picgrid.get(x, y) {
+                if uncovered.is_none() {
+                    if let CellState::Unsolved(_hint) = cell {
+                        uncovered = Some([x, y]);
+                    }
+                }
+                if cell.hint() == PictureGrid::EMPTY {
+                    continue;
+                }
+                let cell_unsolved = picgrid.num_unsolved(x, y);
+                if cell_unsolved > 0 && best.is_none_or(|(_pos, num)| cell_unsolved < num) {
+                    best = Some(([x, y], cell_unsolved));
+                }
+            }
+        }
+    }
+
+    if let Some((pos, _num)) = best {
+        for b in (pos[1] - 1)..(pos[1] + 2) {
+            for a in (pos[0] - 1)..(pos[0] + 2) {
+                if let Some(CellState::Unsolved(_hint)) = picgrid.get(a, b) {
+                    return Some([a, b]);
+                }
+            }
+        }
+    }
+    uncovered
+}
+
+/// Counters gathered while searching.
+#[derive(Default)]
+struct SearchStats {
+    passes: u32,
+    overlaps: u32,
+    guesses: u32,
+    backtracks: u32,
+}
+
+/// Depth-first search for up to `limit` solutions of the grid.
+///
+/// Each branch is propagated with the sweep before it is checked for contradictions,
+/// so guesses are only made once the single clue rules stall.
+fn search(picgrid: PictureGrid, limit: usize, stats: &mut SearchStats) -> Vec<PictureGrid> {
+    let mut solutions = Vec::new();
+    let mut stack = vec![picgrid];
+    while let Some(mut grid) = stack.pop() {
+        let sweep = propagate(&mut grid);
+        stats.passes += sweep.passes();
+        stats.overlaps += sweep.overlaps();
+        if has_contradiction(&grid) {
+            stats.backtracks += 1;
+            continue;
+        }
+        match branch_cell(&grid) {
+            Some(pos) => {
+                stats.guesses += 1;
+                let mut alternative = grid.clone();
+                alternative.set_state(pos[0], pos[1], CellState::Unshaded(0));
+                grid.set_state(pos[0], pos[1], CellState::Shaded(0));
+                stack.push(alternative);
+                stack.push(grid);
+            }
+            None => {
+                solutions.push(grid);
+                if solutions.len() >= limit {
+                    break;
+                }
+            }
+        }
+    }
+    solutions
+}
+
+/// Solutions found while counting the solutions of a puzzle.
+pub struct SolutionCount {
+    /// Solutions found (at most the requested limit)
+    pub solutions: Vec<PictureGrid>,
+    /// Whether the search stopped at the limit (there may be more solutions)
+    pub reached_limit: bool,
+}
+
+impl SolutionCount {
+    /// Returns the number of solutions found
+    pub fn count(&self) -> usize {
+        self.solutions.len()
+    }
+
+    /// Returns if exactly one solution exists
+    pub fn is_unique(&self) -> bool {
+        self.solutions.len() == 1 && !self.reached_limit
+    }
+
+    /// Returns the cells where the first two solutions disagree (empty if fewer than two)
+    pub fn differences(&self) -> Vec<[isize; 2]> {
+        match (self.solutions.first(), self.solutions.get(1)) {
+            (Some(first), Some(second)) => first.differences(second),
+            _ => Vec::new(),
+        }
+    }
+}
+
+/// Counts the solutions of the grid, stopping once `limit` solutions are found.
+///
+/// Cells that are already shaded or unshaded are kept, so a puzzle should be counted
+/// from its unsolved state. A limit of 2 is enough to tell if a solution is unique.
+pub fn count_solutions(picgrid: &PictureGrid, limit: usize) -> SolutionCount {
+    let mut stats = SearchStats::default();
+    let solutions = search(picgrid.clone(), limit, &mut stats);
+    SolutionCount {
+        reached_limit: solutions.len() >= limit,
+        solutions,
+    }
+}
+
+/// Summary of a solver run.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SolveReport {
+    /// Number of passes made over the grid
+    pub passes: u32,
+    /// Number of cells the solver decided (shaded or unshaded)
+    pub cells_decided: usize,
+    /// Number of clue overlap deductions applied
+    pub overlaps: u32,
+    /// Number of guesses made by the search (0 when search is disabled)
+    pub guesses: u32,
+    /// Number of guesses that led to a contradiction
+    pub backtracks: u32,
+    /// Whether every cell of the grid is solved
+    pub is_solved: bool,
+}
+
+/// Runs the solving algorithm to completion without a window.
+pub struct Solver {
+    /// Stores the picture grid being solved.
+    picgrid: PictureGrid,
+    /// Stores if the search fallback is used once the sweep stalls
+    search: bool,
+}
+
+impl Solver {
+    /// Creates a new solver for the picture grid.
+    pub fn new(picgrid: PictureGrid) -> Solver {
+        Solver {
+            picgrid,
+            search: false,
+        }
+    }
+
+    /// Sets if the solver falls back to a backtracking search when the sweep stalls.
+    pub fn with_search(mut self, search: bool) -> Solver {
+        self.search = search;
+        self
+    }
+
+    /// Runs the sweep until it makes no progress, then searches (if enabled).
+    ///
+    /// Returns the resulting grid along with a report of the run. If the search finds
+    /// no solution, the grid is returned as the sweep left it.
+    pub fn solve(mut self) -> (PictureGrid, SolveReport) {
+        let unsolved_before = self.picgrid.num_unsolved_cells();
+
+        let sweep = propagate(&mut self.picgrid);
+        let mut stats = SearchStats {
+            passes: sweep.passes(),
+            overlaps: sweep.overlaps(),
+            ..SearchStats::default()
+        };
+        let mut picgrid = self.picgrid;
+        if self.search && !picgrid.is_solved() {
+            if let Some(solution) = search(picgrid.clone(), 1, &mut stats).pop() {
+                picgrid = solution;
+            }
+        }
+
+        let unsolved_after = picgrid.num_unsolved_cells();
+        let report = SolveReport {
+            passes: stats.passes,
+            cells_decided: unsolved_before - unsolved_after,
+            overlaps: stats.overlaps,
+            guesses: stats.guesses,
+            backtracks: stats.backtracks,
+            is_solved: unsolved_after == 0,
+        };
+        (picgrid, report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use text_format::{parse_text, to_text};
+
+    fn grid(text: &str) -> PictureGrid {
+        parse_text(text).unwrap()
+    }
+
+    #[test]
+    fn search_solves_puzzle_the_sweep_cannot() {
+        let puzzle = grid("...1\n133.\n1..2\n.11.\n");
+        let (swept, report) = Solver::new(puzzle.clone()).solve();
+        assert!(!report.is_solved);
+        assert_eq!(report.guesses, 0);
+        assert!(swept.num_unsolved_cells() > 0);
+
+        let (solved, report) = Solver::new(puzzle).with_search(true).solve();
+        assert!(report.is_solved);
+        assert!(report.guesses > 0);
+        assert!(!has_contradiction(&solved));
+        assert_eq!(
+            to_text(&solved),
+            "...1\n133.\n1..2\n.11.\n\nxxxx\nx##x\nxx#x\nxxxx\n"
+        );
+    }
+
+    #[test]
+    fn search_without_solution_leaves_sweep_result() {
+        // the clue needs 3 shaded cells but only sees 2
+        let (unsolved, report) = Solver::new(grid("3.\n")).with_search(true).solve();
+        assert!(!report.is_solved);
+        assert!(report.backtracks > 0);
+        assert_eq!(unsolved.num_unsolved_cells(), 2);
+    }
+
+    #[test]
+    fn overlap_forces_cells_outside_shared_part() {
+        // 1 sees x 0-2 and 2 sees x 1-3: the shared cells hold exactly one shaded cell
+        let puzzle = grid(".12.\n");
+        assert!(find_clue_deduction(&puzzle, 1, 0).is_none());
+        assert!(find_clue_deduction(&puzzle, 2, 0).is_none());
+
+        let deduction = find_overlap_deduction(&puzzle).unwrap();
+        assert_eq!(deduction.technique, Technique::ClueOverlap);
+        assert_eq!(deduction.clues, vec![[1, 0], [2, 0]]);
+        assert_eq!(
+            deduction.cells,
+            vec![
+                ([0, 0], CellState::Unshaded(0)),
+                ([3, 0], CellState::Shaded(0))
+            ]
+        );
+
+        let mut picgrid = puzzle.clone();
+        deduction.apply(&mut picgrid);
+        assert_eq!(
+            picgrid.get(0, 0),
+            Some(CellState::Unshaded(PictureGrid::EMPTY))
+        );
+        assert_eq!(
+            picgrid.get(3, 0),
+            Some(CellState::Shaded(PictureGrid::EMPTY))
+        );
+    }
+
+    #[test]
+    fn sweep_applies_overlap_when_single_clues_stall() {
+        let mut picgrid = grid(".12.\n");
+        let sweep = propagate(&mut picgrid);
+        assert_eq!(sweep.overlaps(), 1);
+        assert_eq!(sweep.last_technique(), Some(Technique::ClueOverlap));
+        // which of the shared cells is shaded cannot be told
+        assert_eq!(picgrid.num_unsolved_cells(), 2);
+        assert_eq!(
+            picgrid.get(3, 0),
+            Some(CellState::Shaded(PictureGrid::EMPTY))
+        );
+    }
+
+    #[test]
+    fn count_solutions_of_unique_puzzle() {
+        let count = count_solutions(&grid("...1\n133.\n1..2\n.11.\n"), 2);
+        assert_eq!(count.count(), 1);
+        assert!(!count.reached_limit);
+        assert!(count.is_unique());
+        assert!(count.differences().is_empty());
+    }
+
+    #[test]
+    fn count_solutions_stops_at_limit() {
+        // without clues each of the 2 cells can be either, so there are 4 solutions
+        let puzzle = grid("..\n");
+        let count = count_solutions(&puzzle, 2);
+        assert_eq!(count.count(), 2);
+        assert!(count.reached_limit);
+        assert!(!count.is_unique());
+        assert!(!count.differences().is_empty());
+
+        let count = count_solutions(&puzzle, 10);
+        assert_eq!(count.count(), 4);
+        assert!(!count.reached_limit);
+    }
+
+    #[test]
+    fn count_solutions_with_ambiguous_clue() {
+        // the clue sees both cells, only one of which is shaded
+        let count = count_solutions(&grid("1.\n"), 10);
+        assert_eq!(count.count(), 2);
+        assert!(!count.is_unique());
+        assert_eq!(count.differences(), vec![[0, 0], [1, 0]]);
+        assert_eq!(count_solutions(&grid("3.\n"), 10).count(), 0);
+    }
+
+    #[test]
+    fn find_deduction_prefers_single_clue_in_row_major_order() {
+        // the overlap of the 1 and 2 comes first, but the 0 is a single clue deduction
+        let deduction = find_deduction(&grid(".12.0\n")).unwrap();
+        assert_eq!(deduction.technique, Technique::SingleClue);
+        assert_eq!(deduction.clues, vec![[4, 0]]);
+        assert_eq!(
+            deduction.cells,
+            vec![
+                ([3, 0], CellState::Unshaded(0)),
+                ([4, 0], CellState::Unshaded(0))
+            ]
+        );
+
+        // rows before columns
+        let deduction = find_deduction(&grid("...0\n0...\n")).unwrap();
+        assert_eq!(deduction.clues, vec![[3, 0]]);
+
+        let deduction = find_deduction(&grid(".12.\n")).unwrap();
+        assert_eq!(deduction.technique, Technique::ClueOverlap);
+    }
+
+    #[test]
+    fn find_deduction_on_finished_grid() {
+        assert!(find_deduction(&grid("0.\n\nxx\n")).is_none());
+        assert!(find_deduction(&grid("1.\n\n#x\n")).is_none());
+        // complete clues with unsolved cells left
+        assert!(find_deduction(&grid("..\n")).is_none());
+    }
+}
@@ -0,0 +1,263 @@
+//! Compact text puzzle format.
+//!
+//! Each row of the grid is one line: digits are clues and `.` is a cell without a clue.
+//! The cell states can follow after a blank line, one row per line: `#` is shaded, `x` is
+//! unshaded and `.` is unsolved. Without the state block every cell is unsolved. The save
+//! metadata and solution of JSON puzzles have no place in the format.
+//!
+//! ```text
+//! 0.44.
+//! .4.6.
+//! 3.76.
+//!
+//! x.##x
+//! xx##x
+//! .....
+//! ```
+
+use loader::LoadError;
+use picgrid::{CellState, PictureGrid, SizeError};
+
+/// Returns a text parsing error
+fn text_err<T>(line: usize, column: usize, message: &str) -> Result<T, LoadError> {
+    Err(LoadError::Text {
+        line,
+        column,
+        message: message.to_string(),
+    })
+}
+
+/// Parses a puzzle in the text format
+pub fn parse_text(text: &str) -> Result<PictureGrid, LoadError> {
+    // blocks of consecutive non-blank lines, keeping their line numbers (starting at 1)
+    let mut blocks: Vec<Vec<(usize, &str)>> = Vec::new();
+    let mut block = Vec::new();
+    for (number, line) in text.lines().enumerate() {
+        let line = line.trim_end();
+        if line.is_empty() {
+            if !block.is_empty() {
+                blocks.push(block);
+                block = Vec::new();
+            }
+        } else {
+            block.push((number + 1, line));
+        }
+    }
+    if !block.is_empty() {
+        blocks.push(block);
+    }
+
+    let (clues, states) = match blocks.len() {
+        1 => (&blocks[0], None),
+        2 => (&blocks[0], Some(&blocks[1])),
+        0 => return text_err(1, 1, "empty puzzle"),
+        _ => {
+            return text_err(
+                blocks[2][0].0,
+                1,
+                "expected at most a clue block and a state block",
+            )
+        }
+    };
+
+    let width = clues[0].1.chars().count();
+    let height = clues.len();
+    let size = if width > u16::MAX as usize || height > u16::MAX as usize {
+        Err(SizeError::TooLarge {
+            width: width.min(u16::MAX as usize) as u16,
+            height: height.min(u16::MAX as usize) as u16,
+        })
+    } else {
+        PictureGrid::check_size(width as u16, height as u16)
+    };
+    if let Err(err) = size {
+        return text_err(clues[0].0, 1, &err.to_string());
+    }
+    let check_width = |block: &[(usize, &str)], name: &str| -> Result<(), LoadError> {
+        for (number, line) in block.iter() {
+            if line.chars().count() != width {
+                return text_err(
+                    *number,
+                    1,
+                    &format!(
+                        "{} row has {} cells, expected {}",
+                        name,
+                        line.chars().count(),
+                        width
+                    ),
+                );
+            }
+        }
+        Ok(())
+    };
+    check_width(clues, "clue")?;
+    if let Some(states) = states {
+        check_width(states, "state")?;
+        if states.len() != height {
+            return text_err(
+                states[0].0,
+                1,
+                &format!("state block has {} rows, expected {}", states.len(), height),
+            );
+        }
+    }
+
+    let mut cells = Vec::with_capacity(width * height);
+    for (number, line) in clues.iter() {
+        for (x, symbol) in line.chars().enumerate() {
+            let hint = match symbol {
+                '0'..='9' => symbol as u8 - b'0',
+                '.' => PictureGrid::EMPTY,
+                _ => return text_err(*number, x + 1, &format!("unexpected clue '{}'", symbol)),
+            };
+            cells.push(CellState::Unsolved(hint));
+        }
+    }
+
+    if let Some(states) = states {
+        for (y, (number, line)) in states.iter().enumerate() {
+            for (x, symbol) in line.chars().enumerate() {
+                let index = y * width + x;
+                let hint = cells[index].hint();
+                let state = match symbol {
+                    '#' => CellState::Shaded(hint),
+                    'x' | 'X' => CellState::Unshaded(hint),
+                    '.' | '?' => CellState::Unsolved(hint),
+                    _ => {
+                        return text_err(*number, x + 1, &format!("unexpected state '{}'", symbol))
+                    }
+                };
+                cells[index] = state;
+            }
+        }
+    }
+
+    let mut picgrid = PictureGrid::new(width as u16, height as u16);
+    picgrid.with_values(cells);
+    Ok(picgrid)
+}
+
+/// Writes a puzzle in the text format.
+///
+/// The state block is only written when at least one cell is solved. The save metadata and
+/// the solution are left out.
+pub fn to_text(picgrid: &PictureGrid) -> String {
+    let mut clues = String::new();
+    let mut states = String::new();
+    for y in 0..picgrid.height as isize {
+        for x in 0..picgrid.width as isize {
+            let cell = picgrid
+                .get(x, y)
+                .unwrap_or(CellState::Unsolved(PictureGrid::EMPTY));
+            clues.push(match cell.hint() {
+                hint if hint <= 9 => (b'0' + hint) as char,
+                _ => '.',
+            });
+            states.push(match cell {
+                CellState::Unsolved(_hint) => '.',
+                CellState::Shaded(_hint) => '#',
+                CellState::Unshaded(_hint) => 'x',
+            });
+        }
+        clues.push('\n');
+        states.push('\n');
+    }
+
+    if picgrid.num_unsolved_cells() < picgrid.cells.len() {
+        clues.push('\n');
+        clues.push_str(&states);
+    }
+    clues
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use loader::load_str;
+
+    /// Returns the position and message of a text parsing error
+    fn text_error(text: &str) -> (usize, usize, String) {
+        match parse_text(text) {
+            Err(LoadError::Text {
+                line,
+                column,
+                message,
+            }) => (line, column, message),
+            Err(other) => panic!("unexpected error {:?}", other),
+            Ok(_picgrid) => panic!("puzzle was parsed"),
+        }
+    }
+
+    #[test]
+    fn parses_clues_and_states() {
+        let picgrid = parse_text("0.4\n.9.\n\n#x.\n..#\n").unwrap();
+        assert_eq!((picgrid.width, picgrid.height), (3, 2));
+        assert_eq!(picgrid.get(0, 0), Some(CellState::Shaded(0)));
+        assert_eq!(
+            picgrid.get(1, 0),
+            Some(CellState::Unshaded(PictureGrid::EMPTY))
+        );
+        assert_eq!(picgrid.get(2, 0), Some(CellState::Unsolved(4)));
+        assert_eq!(picgrid.get(1, 1), Some(CellState::Unsolved(9)));
+        assert_eq!(
+            picgrid.get(2, 1),
+            Some(CellState::Shaded(PictureGrid::EMPTY))
+        );
+        assert_eq!(to_text(&picgrid), "0.4\n.9.\n\n#x.\n..#\n");
+    }
+
+    #[test]
+    fn reports_parse_errors() {
+        assert_eq!(text_error("\n  \n"), (1, 1, "empty puzzle".to_string()));
+        assert_eq!(
+            text_error("0.\n\n..\n\n..\n"),
+            (
+                5,
+                1,
+                "expected at most a clue block and a state block".to_string()
+            )
+        );
+        assert_eq!(
+            text_error("0.\n0..\n"),
+            (2, 1, "clue row has 3 cells, expected 2".to_string())
+        );
+        assert_eq!(
+            text_error("0.\n1a\n"),
+            (2, 2, "unexpected clue 'a'".to_string())
+        );
+        assert_eq!(
+            text_error("0.\n1.\n\n#x\n"),
+            (4, 1, "state block has 1 rows, expected 2".to_string())
+        );
+        assert_eq!(
+            text_error("0.\n\n#o\n"),
+            (3, 2, "unexpected state 'o'".to_string())
+        );
+    }
+
+    #[test]
+    fn detects_format() {
+        let picgrid =
+            load_str("  {\"width\": 1, \"height\": 1, \"cells\": {\"0\": {\"Unsolved\": 0}}}")
+                .unwrap();
+        assert_eq!(picgrid.get(0, 0), Some(CellState::Unsolved(0)));
+        let picgrid = load_str("\n0\n").unwrap();
+        assert_eq!(picgrid.get(0, 0), Some(CellState::Unsolved(0)));
+        match load_str("{0\n") {
+            Err(LoadError::Syntax { .. }) => {}
+            other => panic!("unexpected result {:?}", other.err()),
+        }
+        match load_str("x\n") {
+            Err(LoadError::Text { .. }) => {}
+            other => panic!("unexpected result {:?}", other.err()),
+        }
+    }
+
+    #[test]
+    fn text_test_file_matches_json() {
+        let json = load_str(include_str!("../test_files/original-5x5.json")).unwrap();
+        let text = include_str!("../test_files/original-5x5.txt");
+        assert_eq!(parse_text(text).unwrap().cells, json.cells);
+        assert_eq!(to_text(&json), text);
+    }
+}
@@ -0,0 +1,278 @@
+//! Color themes and the settings file they are selected in.
+
+use std::fs::File;
+use std::io;
+use std::path::Path;
+
+use serde_json;
+
+use view_settings::{Color, PictureGridViewSettings};
+
+/// Named set of the colors used by the window (and image export from the window).
+///
+/// Colors missing from a theme in the settings file are taken from the light theme, but the
+/// name is required.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default = "Theme::unnamed")]
+pub struct Theme {
+    /// Name the theme is selected by
+    pub name: String,
+    /// color of the window background
+    pub background_color: Color,
+    /// color of control label text
+    pub label_color: Color,
+    /// color of grid borders
+    pub grid_border_color: Color,
+    /// color of hint text (when cell is unsolved)
+    pub cell_unsolved_hint_text_color: Color,
+    /// background color of cell (when cell is unsolved)
+    pub cell_unsolved_background_color: Color,
+    /// color of hint text (when cell is solved as shaded)
+    pub cell_solved_shaded_hint_text_color: Color,
+    /// background color of cell (when cell is solved as shaded)
+    pub cell_solved_shaded_background_color: Color,
+    /// color of hint text (when cell is solved as unshaded)
+    pub cell_solved_unshaded_hint_text_color: Color,
+    /// background color of cell (when cell is solved as unshaded)
+    pub cell_solved_unshaded_background_color: Color,
+    /// color of current cell being processed by algorithm
+    pub cell_current_color: Color,
+    /// color of hint text and outline (when clue can no longer be satisfied)
+    pub cell_violation_color: Color,
+    /// color of the keyboard cursor outline
+    pub cell_cursor_color: Color,
+    /// color of the outline around the 3x3 area of the hovered cell
+    pub cell_neighborhood_color: Color,
+    /// color of the outline around the clues and forced cells of a hint
+    pub cell_hint_color: Color,
+    /// color of the outline around cells that differ from the solution
+    pub cell_wrong_color: Color,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self::light()
+    }
+}
+
+impl Theme {
+    /// Creates the light theme (the colors of `PictureGridViewSettings::new()`).
+    pub fn light() -> Theme {
+        let settings = PictureGridViewSettings::new();
+        Theme {
+            name: "light".to_string(),
+            background_color: settings.background_color,
+            label_color: settings.label_color,
+            grid_border_color: settings.grid_border_color,
+            cell_unsolved_hint_text_color: settings.cell_unsolved_hint_text_color,
+            cell_unsolved_background_color: settings.cell_unsolved_background_color,
+            cell_solved_shaded_hint_text_color: settings.cell_solved_shaded_hint_text_color,
+            cell_solved_shaded_background_color: settings.cell_solved_shaded_background_color,
+            cell_solved_unshaded_hint_text_color: settings.cell_solved_unshaded_hint_text_color,
+            cell_solved_unshaded_background_color: settings.cell_solved_unshaded_background_color,
+            cell_current_color: settings.cell_current_color,
+            cell_violation_color: settings.cell_violation_color,
+            cell_cursor_color: settings.cell_cursor_color,
+            cell_neighborhood_color: settings.cell_neighborhood_color,
+            cell_hint_color: settings.cell_hint_color,
+            cell_wrong_color: settings.cell_wrong_color,
+        }
+    }
+
+    /// Creates the light theme without a name (for themes of the settings file that leave it out)
+    fn unnamed() -> Theme {
+        Theme {
+            name: String::new(),
+            ..Theme::light()
+        }
+    }
+
+    /// Creates the dark theme, for dim rooms.
+    pub fn dark() -> Theme {
+        Theme {
+            name: "dark".to_string(),
+            background_color: [0.12, 0.12, 0.14, 1.0],
+            label_color: [0.85, 0.85, 0.85, 1.0],
+            grid_border_color: [0.5, 0.5, 0.55, 1.0],
+            cell_unsolved_hint_text_color: [0.95, 0.95, 0.95, 1.0],
+            cell_unsolved_background_color: [0.3, 0.3, 0.34, 1.0],
+            cell_solved_shaded_hint_text_color: [0.7, 0.7, 0.7, 1.0],
+            cell_solved_shaded_background_color: [0.05, 0.05, 0.06, 1.0],
+            cell_solved_unshaded_hint_text_color: [0.5, 0.5, 0.55, 1.0],
+            cell_solved_unshaded_background_color: [0.18, 0.18, 0.2, 1.0],
+            cell_current_color: [1.0, 0.35, 0.3, 1.0],
+            cell_violation_color: [1.0, 0.4, 0.75, 1.0],
+            cell_cursor_color: [0.35, 0.65, 1.0, 1.0],
+            cell_neighborhood_color: [0.95, 0.75, 0.2, 1.0],
+            cell_hint_color: [0.4, 0.85, 0.5, 1.0],
+            cell_wrong_color: [1.0, 0.35, 0.35, 1.0],
+        }
+    }
+
+    /// Creates the high-contrast theme (black, white and saturated highlights).
+    pub fn high_contrast() -> Theme {
+        Theme {
+            name: "high-contrast".to_string(),
+            background_color: [1.0; 4],
+            label_color: [0.0, 0.0, 0.0, 1.0],
+            grid_border_color: [0.0, 0.0, 0.0, 1.0],
+            cell_unsolved_hint_text_color: [0.0, 0.0, 0.0, 1.0],
+            cell_unsolved_background_color: [1.0; 4],
+            cell_solved_shaded_hint_text_color: [1.0; 4],
+            cell_solved_shaded_background_color: [0.0, 0.0, 0.0, 1.0],
+            cell_solved_unshaded_hint_text_color: [0.0, 0.0, 0.0, 1.0],
+            cell_solved_unshaded_background_color: [0.7, 0.7, 0.7, 1.0],
+            cell_current_color: [1.0, 0.0, 0.0, 1.0],
+            cell_violation_color: [1.0, 0.0, 1.0, 1.0],
+            cell_cursor_color: [0.0, 0.0, 1.0, 1.0],
+            cell_neighborhood_color: [1.0, 0.5, 0.0, 1.0],
+            cell_hint_color: [0.0, 0.6, 0.0, 1.0],
+            cell_wrong_color: [1.0, 0.0, 0.0, 1.0],
+        }
+    }
+
+    /// Creates the color-blind friendly theme (highlights from the Okabe-Ito palette, which
+    /// stay distinct with red-green color blindness).
+    pub fn color_blind() -> Theme {
+        Theme {
+            name: "color-blind".to_string(),
+            cell_current_color: [0.9, 0.62, 0.0, 1.0],
+            cell_violation_color: [0.84, 0.37, 0.0, 1.0],
+            cell_cursor_color: [0.0, 0.45, 0.7, 1.0],
+            cell_neighborhood_color: [0.0, 0.62, 0.45, 1.0],
+            cell_hint_color: [0.8, 0.47, 0.65, 1.0],
+            cell_wrong_color: [0.34, 0.71, 0.91, 1.0],
+            ..Theme::light()
+        }
+    }
+
+    /// Returns the themes that are always available
+    pub fn built_in() -> Vec<Theme> {
+        vec![
+            Theme::light(),
+            Theme::dark(),
+            Theme::high_contrast(),
+            Theme::color_blind(),
+        ]
+    }
+
+    /// Sets the colors of the view settings to the colors of the theme
+    pub fn apply(&self, settings: &mut PictureGridViewSettings) {
+        settings.background_color = self.background_color;
+        settings.label_color = self.label_color;
+        settings.grid_border_color = self.grid_border_color;
+        settings.cell_unsolved_hint_text_color = self.cell_unsolved_hint_text_color;
+        settings.cell_unsolved_background_color = self.cell_unsolved_background_color;
+        settings.cell_solved_shaded_hint_text_color = self.cell_solved_shaded_hint_text_color;
+        settings.cell_solved_shaded_background_color = self.cell_solved_shaded_background_color;
+        settings.cell_solved_unshaded_hint_text_color = self.cell_solved_unshaded_hint_text_color;
+        settings.cell_solved_unshaded_background_color = self.cell_solved_unshaded_background_color;
+        settings.cell_current_color = self.cell_current_color;
+        settings.cell_violation_color = self.cell_violation_color;
+        settings.cell_cursor_color = self.cell_cursor_color;
+        settings.cell_neighborhood_color = self.cell_neighborhood_color;
+        settings.cell_hint_color = self.cell_hint_color;
+        settings.cell_wrong_color = self.cell_wrong_color;
+    }
+}
+
+/// Contents of the settings file (JSON).
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ThemeSettings {
+    /// Name of the theme used at startup (the first theme if None)
+    pub theme: Option<String>,
+    /// Themes added to the built-in themes (replacing a built-in theme of the same name)
+    pub themes: Vec<Theme>,
+}
+
+impl ThemeSettings {
+    /// Loads the settings file, which fails if a theme has no name
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<ThemeSettings> {
+        let file = File::open(path)?;
+        let settings: ThemeSettings = serde_json::from_reader(file).map_err(io::Error::from)?;
+        if let Some(index) = settings
+            .themes
+            .iter()
+            .position(|theme| theme.name.trim().is_empty())
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("theme {} has no name", index + 1),
+            ));
+        }
+        Ok(settings)
+    }
+
+    /// Returns the built-in themes followed by the themes of the file
+    pub fn all_themes(&self) -> Vec<Theme> {
+        let mut themes = Theme::built_in();
+        for theme in &self.themes {
+            match themes
+                .iter()
+                .position(|known| known.name.eq_ignore_ascii_case(&theme.name))
+            {
+                Some(index) => themes[index] = theme.clone(),
+                None => themes.push(theme.clone()),
+            }
+        }
+        themes
+    }
+
+    /// Finds the position of a theme (ignoring case) in `all_themes`
+    pub fn find_theme(&self, name: &str) -> Option<usize> {
+        self.all_themes()
+            .iter()
+            .position(|theme| theme.name.eq_ignore_ascii_case(name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs;
+
+    /// Writes a settings file to the temporary directory and loads it
+    fn load(name: &str, json: &str) -> io::Result<ThemeSettings> {
+        let path = env::temp_dir().join(format!("fill-a-pix-{}-{}.json", name, std::process::id()));
+        fs::write(&path, json)?;
+        let settings = ThemeSettings::load(&path);
+        fs::remove_file(&path)?;
+        settings
+    }
+
+    #[test]
+    fn theme_takes_missing_colors_from_light() {
+        let settings = load(
+            "partial",
+            r#"{"theme": "Sepia", "themes": [{"name": "sepia", "background_color": [0, 0, 0, 1]}]}"#,
+        )
+        .unwrap();
+        let sepia = &settings.themes[0];
+        assert_eq!(sepia.background_color, [0.0, 0.0, 0.0, 1.0]);
+        assert_eq!(sepia.label_color, Theme::light().label_color);
+        assert_eq!(settings.find_theme("Sepia"), Some(4));
+    }
+
+    #[test]
+    fn theme_replaces_built_in_theme_of_same_name() {
+        let settings = load("replace", r#"{"themes": [{"name": "Dark"}]}"#).unwrap();
+        let themes = settings.all_themes();
+        assert_eq!(themes.len(), 4);
+        assert_eq!(themes[1].name, "Dark");
+        assert_eq!(themes[1].background_color, Theme::light().background_color);
+        assert_eq!(themes[0], Theme::light());
+    }
+
+    #[test]
+    fn theme_without_name_is_rejected() {
+        let err = load(
+            "unnamed",
+            r#"{"themes": [{"name": "a"}, {"label_color": [1, 0, 0, 1]}]}"#,
+        )
+        .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(err.to_string(), "theme 2 has no name");
+    }
+}
@@ -0,0 +1,97 @@
+//! Colors and sizes shared by the window and image export.
+
+/// RGBA color with components from 0 to 1 (the same as piston's `types::Color`)
+pub type Color = [f32; 4];
+
+/// Stores picgrid view settings.
+pub struct PictureGridViewSettings {
+    /// (x,y) position of upper left corner of controls area
+    pub controls_position: [f64; 2],
+    /// (x,y) position of upper left corner of grid
+    pub grid_position: [f64; 2],
+    /// (h,v) side margins within view port
+    pub margin: [f64; 2],
+    /// color of the window background
+    pub background_color: Color,
+    /// size of label text
+    pub label_size: u32,
+    /// color of control label text
+    pub label_color: Color,
+    /// width/height of grid cells (only if viewport cannot be determined)
+    pub cell_size: f64,
+    /// color of grid borders
+    pub grid_border_color: Color,
+    /// width of outer grid border
+    pub grid_border_width: f64,
+    /// width of cell grid border
+    pub cell_border_width: f64,
+    /// color of hint text (when cell is unsolved)
+    pub cell_unsolved_hint_text_color: Color,
+    /// background color of cell (when cell is unsolved)
+    pub cell_unsolved_background_color: Color,
+    /// color of hint text (when cell is solved as shaded)
+    pub cell_solved_shaded_hint_text_color: Color,
+    /// background color of cell (when cell is solved as shaded)
+    pub cell_solved_shaded_background_color: Color,
+    /// color of hint text (when cell is solved as unshaded)
+    pub cell_solved_unshaded_hint_text_color: Color,
+    /// background color of cell (when cell is solved as unshaded)
+    pub cell_solved_unshaded_background_color: Color,
+    /// color of current cell being processed by algorithm
+    pub cell_current_color: Color,
+    /// color of hint text and outline (when clue can no longer be satisfied)
+    pub cell_violation_color: Color,
+    /// color of the keyboard cursor outline
+    pub cell_cursor_color: Color,
+    /// color of the outline around the 3x3 area of the hovered cell
+    pub cell_neighborhood_color: Color,
+    /// color of the outline around the clues and forced cells of a hint
+    pub cell_hint_color: Color,
+    /// color of the outline around cells that differ from the solution
+    pub cell_wrong_color: Color,
+    /// opacity of hint text (when the 3x3 area of the cell is complete)
+    pub complete_hint_text_opacity: f32,
+}
+
+impl Default for PictureGridViewSettings {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PictureGridViewSettings {
+    /// Creates new picgrid view settings.
+    pub fn new() -> PictureGridViewSettings {
+        PictureGridViewSettings {
+            controls_position: [15.0; 2],
+            grid_position: [260.0, 15.0],
+            margin: [15.0; 2],
+            background_color: [0.89, 0.87, 0.73, 1.0],
+            label_size: 15,
+            label_color: [0.0, 0.0, 0.0, 1.0],
+            cell_size: 50.0,
+            grid_border_color: [0.4, 0.4, 0.4, 1.0],
+            grid_border_width: 3.0,
+            cell_border_width: 1.0,
+            cell_unsolved_hint_text_color: [0.0, 0.0, 0.0, 1.0],
+            cell_unsolved_background_color: [1.0; 4],
+            cell_solved_shaded_hint_text_color: [1.0; 4],
+            cell_solved_shaded_background_color: [0.0, 0.0, 0.0, 1.0],
+            cell_solved_unshaded_hint_text_color: [0.5, 0.5, 0.5, 1.0],
+            cell_solved_unshaded_background_color: [0.9, 0.9, 0.9, 1.0],
+            cell_current_color: [1.0, 0.0, 0.0, 1.0],
+            cell_violation_color: [0.85, 0.1, 0.55, 1.0],
+            cell_cursor_color: [0.1, 0.45, 0.9, 1.0],
+            cell_neighborhood_color: [0.95, 0.6, 0.1, 1.0],
+            cell_hint_color: [0.1, 0.65, 0.3, 1.0],
+            cell_wrong_color: [0.85, 0.1, 0.1, 1.0],
+            complete_hint_text_opacity: 0.35,
+        }
+    }
+}
+
+/// Finds where the hint of a cell is drawn: the text size and the baseline start of the text
+/// relative to the upper left corner of the cell.
+pub fn hint_text_layout(cell_size: f64) -> (f64, [f64; 2]) {
+    (cell_size * 0.75, [cell_size * 0.30, cell_size * 0.75])
+}
@@ -0,0 +1,133 @@
+//! Boundary tests for large grid sizes
+
+extern crate fill_a_pix_rust;
+
+use fill_a_pix_rust::generator::clues_from_picture;
+use fill_a_pix_rust::loader::load_str;
+use fill_a_pix_rust::{
+    parse_text, to_text, CellState, LoadError, PictureGrid, SchemaError, SizeError, Solver,
+};
+
+#[test]
+fn check_size_boundaries() {
+    assert_eq!(PictureGrid::check_size(1, 1), Ok(()));
+    assert_eq!(PictureGrid::check_size(4096, 4096), Ok(()));
+    assert_eq!(PictureGrid::check_size(65535, 256), Ok(()));
+    assert_eq!(PictureGrid::check_size(256, 65535), Ok(()));
+    assert_eq!(
+        PictureGrid::check_size(4096, 4097),
+        Err(SizeError::TooLarge {
+            width: 4096,
+            height: 4097
+        })
+    );
+    assert_eq!(
+        PictureGrid::check_size(65535, 65535),
+        Err(SizeError::TooLarge {
+            width: 65535,
+            height: 65535
+        })
+    );
+    assert_eq!(
+        PictureGrid::check_size(0, 5),
+        Err(SizeError::Empty {
+            width: 0,
+            height: 5
+        })
+    );
+    assert!(PictureGrid::try_new(5, 0).is_err());
+}
+
+#[test]
+fn grid_larger_than_u16_cells() {
+    // 300x300 = 90000 cells, more than fit in a u16
+    let mut picgrid = PictureGrid::try_new(300, 300).unwrap();
+    assert_eq!(picgrid.cells.len(), 90000);
+
+    picgrid.set(299, 299, CellState::Shaded(3));
+    picgrid.set(0, 299, CellState::Unshaded(PictureGrid::EMPTY));
+    assert_eq!(picgrid.get(299, 299), Some(CellState::Shaded(3)));
+    assert_eq!(picgrid.cells[89999], CellState::Shaded(3));
+    assert_eq!(
+        picgrid.cells[299 * 300],
+        CellState::Unshaded(PictureGrid::EMPTY)
+    );
+    assert_eq!(
+        picgrid.get(300, 299),
+        Some(CellState::Unshaded(PictureGrid::EMPTY))
+    );
+    assert_eq!(
+        picgrid.get(299, 300),
+        Some(CellState::Unshaded(PictureGrid::EMPTY))
+    );
+    assert_eq!(picgrid.num_unsolved_cells(), 89998);
+}
+
+#[test]
+fn widest_supported_grid() {
+    let mut picgrid = PictureGrid::try_new(65535, 2).unwrap();
+    picgrid.set(65534, 1, CellState::Shaded(0));
+    assert_eq!(picgrid.get(65534, 1), Some(CellState::Shaded(0)));
+    assert_eq!(picgrid.cells[2 * 65535 - 1], CellState::Shaded(0));
+    assert_eq!(picgrid.num_unsolved_cells(), 2 * 65535 - 1);
+}
+
+#[test]
+fn solve_large_grid() {
+    // a frame of shaded cells around an unshaded 300x300 picture
+    let (width, height) = (300u16, 300u16);
+    let picture: Vec<bool> = (0..width as usize * height as usize)
+        .map(|index| {
+            let (x, y) = (index % width as usize, index / width as usize);
+            x == 0 || y == 0 || x == width as usize - 1 || y == height as usize - 1
+        })
+        .collect();
+    let picgrid = clues_from_picture(width, height, &picture);
+    let (solved, report) = Solver::new(picgrid).solve();
+    assert!(report.is_solved);
+    assert_eq!(solved.get(299, 299), Some(CellState::Shaded(3)));
+    assert_eq!(solved.get(150, 150), Some(CellState::Unshaded(0)));
+}
+
+#[test]
+fn load_rejects_unsupported_sizes() {
+    match load_str(r#"{"width":65535,"height":65535,"cells":{}}"#) {
+        Err(LoadError::Schema(errors)) => assert_eq!(
+            errors,
+            vec![SchemaError::Size(SizeError::TooLarge {
+                width: 65535,
+                height: 65535
+            })]
+        ),
+        _ => panic!("expected a schema error"),
+    }
+    match load_str(r#"{"width":0,"height":0,"cells":{}}"#) {
+        Err(LoadError::Schema(errors)) => assert_eq!(
+            errors,
+            vec![SchemaError::Size(SizeError::Empty {
+                width: 0,
+                height: 0
+            })]
+        ),
+        _ => panic!("expected a schema error"),
+    }
+}
+
+#[test]
+fn text_format_round_trip_large_grid() {
+    let mut picgrid = PictureGrid::try_new(300, 300).unwrap();
+    picgrid.set(299, 299, CellState::Shaded(9));
+    picgrid.set(0, 0, CellState::Unshaded(0));
+    let loaded = parse_text(&to_text(&picgrid)).unwrap();
+    assert_eq!((loaded.width, loaded.height), (300, 300));
+    assert_eq!(loaded.cells, picgrid.cells);
+}
+
+#[test]
+fn text_format_rejects_unsupported_sizes() {
+    let row = ".".repeat(70000);
+    match parse_text(&row) {
+        Err(LoadError::Text { line, .. }) => assert_eq!(line, 1),
+        _ => panic!("expected a text error"),
+    }
+}